@@ -1,3 +1,27 @@
+use std::path::Path;
+
+#[allow(dead_code)]
+#[path = "src/layout.rs"]
+mod layout;
+
+// Deja el JSON Schema del layout y del style junto a los schemas de capabilities,
+// para que el backend valide lo que publica en el broker.
+fn write_protocol_schemas() {
+    println!("cargo:rerun-if-changed=src/layout.rs");
+
+    let dir = Path::new("gen/schemas");
+    std::fs::create_dir_all(dir).expect("no se pudo crear gen/schemas");
+    let docs = [
+        ("ui-layout.schema.json", layout::layout_schema()),
+        ("ui-style.schema.json", layout::style_schema()),
+    ];
+    for (name, schema) in docs {
+        let txt = serde_json::to_string_pretty(&schema).expect("schema serializable");
+        std::fs::write(dir.join(name), txt).expect("no se pudo escribir el schema");
+    }
+}
+
 fn main() {
+    write_protocol_schemas();
     tauri_build::build()
 }
@@ -0,0 +1,312 @@
+// ACKs de cada mensaje del broker hacia el ACK endpoint (HTTP POST).
+// Cola acotada en memoria: si el endpoint no responde y se llena,
+// se descarta el ACK más viejo. Un worker async los entrega en orden con reintentos;
+// un ACK que el endpoint rechaza (4xx) o que no sale tras MAX_ROUNDS rondas se descarta
+// para no trabar a los que vienen detrás.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use serde::Serialize;
+use tokio::sync::Notify;
+use tokio::time::sleep;
+
+use crate::redact;
+use crate::state::AppState;
+
+const QUEUE_CAPACITY: usize = 256;
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_BASE: Duration = Duration::from_millis(500);
+const PAUSE_AFTER_FAILURE: Duration = Duration::from_secs(5);
+const MAX_ROUNDS: u32 = 5; // rondas de MAX_ATTEMPTS (con su pausa) antes de descartar
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AckOutcome {
+    Applied,          // layout / comando aplicado
+    FallbackRestored, // layout inválido → se re-emitió last_good
+    NoLayoutFound,    // JSON válido pero sin layout ni comando conocido
+    Invalid,          // frames ilegibles (no UTF-8 / JSON roto)
+    Duplicate,        // msg_id ya procesado → ignorado
+    OutOfOrder,       // seq menor o igual al último aplicado de ese source
+    Expired,          // issued_at + ttl_ms ya pasó → no se aplicó
+    Unauthenticated,  // sin firma (y se exige) o firma inválida / kid desconocido
+    UnknownCommand,   // cmd.name sin handler registrado
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Ack {
+    #[serde(skip)]
+    seq: u64,
+    pub msg_id: Option<String>,
+    pub command: Option<String>,
+    pub outcome: AckOutcome,
+    pub error: Option<String>,
+    pub device_id: String, // lo completa ack_worker con la config
+    pub at_millis: i64,
+}
+
+impl Ack {
+    pub fn new(msg_id: Option<String>, command: Option<String>, outcome: AckOutcome, error: Option<String>) -> Self {
+        Self {
+            seq: 0,
+            msg_id,
+            command,
+            outcome,
+            // el detalle puede citar el frame (valores de un layout rechazado, etc.)
+            error: error.map(|e| redact::text(&e)),
+            device_id: String::new(),
+            at_millis: Utc::now().timestamp_millis(),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct AckQueue {
+    inner: Arc<Mutex<AckQueueInner>>,
+    notify: Arc<Notify>,
+}
+
+#[derive(Default)]
+struct AckQueueInner {
+    items: VecDeque<Ack>,
+    next_seq: u64,
+    dropped: u64,
+    disabled: bool, // features.acks = false: no se encola nada
+}
+
+impl AckQueue {
+    // No bloquea: se puede llamar desde el hilo del listener ZMQ
+    pub fn push(&self, mut ack: Ack) {
+        {
+            let mut q = self.inner.lock().unwrap();
+            if q.disabled {
+                return;
+            }
+            q.next_seq += 1;
+            ack.seq = q.next_seq;
+            if q.items.len() >= QUEUE_CAPACITY {
+                q.items.pop_front();
+                q.dropped += 1;
+                eprintln!("[ACK] cola llena; descartado el ACK más viejo ({} en total)", q.dropped);
+            }
+            q.items.push_back(ack);
+        }
+        self.notify.notify_one();
+    }
+
+    pub fn disable(&self) {
+        let mut q = self.inner.lock().unwrap();
+        q.disabled = true;
+        q.items.clear();
+    }
+
+    fn front(&self) -> Option<Ack> {
+        self.inner.lock().unwrap().items.front().cloned()
+    }
+
+    // Sólo saca el ACK si sigue al frente (pudo haberse descartado mientras se enviaba)
+    fn remove(&self, seq: u64) {
+        let mut q = self.inner.lock().unwrap();
+        if q.items.front().map(|a| a.seq) == Some(seq) {
+            q.items.pop_front();
+        }
+    }
+
+    // Igual que remove, pero cuenta como perdido
+    fn discard(&self, seq: u64) {
+        let mut q = self.inner.lock().unwrap();
+        if q.items.front().map(|a| a.seq) == Some(seq) {
+            q.items.pop_front();
+            q.dropped += 1;
+        }
+    }
+}
+
+#[derive(Debug)]
+struct DeliveryError {
+    message: String,
+    permanent: bool, // reintentar no va a cambiar nada
+}
+
+// 4xx = el endpoint no lo va a aceptar nunca; 408 y 429 sí son de "probá más tarde"
+fn is_permanent(status: reqwest::StatusCode) -> bool {
+    status.is_client_error() && !matches!(status.as_u16(), 408 | 429)
+}
+
+async fn deliver(client: &reqwest::Client, url: &str, ack: &Ack) -> Result<(), DeliveryError> {
+    let mut last_err = String::new();
+    for attempt in 0..MAX_ATTEMPTS {
+        if attempt > 0 {
+            sleep(RETRY_BASE * 2u32.pow(attempt - 1)).await;
+        }
+        match client.post(url).json(ack).send().await {
+            Ok(resp) if resp.status().is_success() => return Ok(()),
+            Ok(resp) if is_permanent(resp.status()) => {
+                return Err(DeliveryError { message: format!("HTTP {}", resp.status()), permanent: true });
+            }
+            Ok(resp) => last_err = format!("HTTP {}", resp.status()),
+            Err(e) => last_err = e.to_string(),
+        }
+    }
+    Err(DeliveryError { message: last_err, permanent: false })
+}
+
+pub async fn ack_worker(state: AppState) {
+    let config = state.config();
+    let device_id = config.device_id();
+
+    let client = match reqwest::Client::builder().timeout(Duration::from_secs(config.ack.timeout_secs)).build() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("[ACK] no se pudo crear el cliente HTTP: {e}");
+            return;
+        }
+    };
+    let queue = state.acks.clone();
+    eprintln!("[ACK] enviando ACKs a {}", config.ack.url);
+
+    let mut failing: (u64, u32) = (0, 0); // (seq del ACK al frente, rondas fallidas)
+    loop {
+        let Some(mut ack) = queue.front() else {
+            queue.notify.notified().await;
+            continue;
+        };
+        ack.device_id.clone_from(&device_id);
+        // se relee en cada envío: config.update puede cambiar el endpoint
+        let url = state.config().ack.url;
+        *state.ack_endpoint_snapshot.lock().unwrap() = url.clone();
+        match deliver(&client, &url, &ack).await {
+            Ok(()) => queue.remove(ack.seq),
+            Err(e) if e.permanent => {
+                eprintln!("[ACK] ❌ ACK {:?} rechazado ({}); se descarta", ack.msg_id, e.message);
+                queue.discard(ack.seq);
+            }
+            Err(e) => {
+                let rounds = if failing.0 == ack.seq { failing.1 + 1 } else { 1 };
+                failing = (ack.seq, rounds);
+                if rounds >= MAX_ROUNDS {
+                    eprintln!("[ACK] ❌ ACK {:?} sin entregar tras {rounds} rondas ({}); se descarta", ack.msg_id, e.message);
+                    queue.discard(ack.seq);
+                    continue;
+                }
+                // se queda al frente; la cola acotada evita crecer sin límite
+                eprintln!("[ACK] ❌ no se pudo entregar ACK {:?}: {}", ack.msg_id, e.message);
+                sleep(PAUSE_AFTER_FAILURE).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn ack_error_is_masked() {
+        let ack = Ack::new(
+            Some("m1".into()),
+            Some("ui.apply".into()),
+            AckOutcome::Invalid,
+            Some("$.root.children[0].text: valor raro '4111111111111111'".into()),
+        );
+        assert_eq!(ack.error.as_deref(), Some("$.root.children[0].text: valor raro '411111******1111'"));
+        assert_eq!(ack.msg_id.as_deref(), Some("m1"));
+    }
+
+    // Stand-in del ACK endpoint: contesta con los status de `statuses` en orden
+    // (el último se repite) y guarda los bodies recibidos.
+    fn stand_in(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<String>>>, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/ack", listener.local_addr().unwrap());
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let hits = Arc::new(AtomicUsize::new(0));
+        let (b, h) = (bodies.clone(), hits.clone());
+        std::thread::spawn(move || {
+            for mut conn in listener.incoming().flatten() {
+                let n = h.fetch_add(1, Ordering::SeqCst);
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 4096];
+                let body = loop {
+                    let k = conn.read(&mut chunk).unwrap_or(0);
+                    buf.extend_from_slice(&chunk[..k]);
+                    let txt = String::from_utf8_lossy(&buf).to_string();
+                    if let Some(i) = txt.find("\r\n\r\n") {
+                        let len = txt
+                            .lines()
+                            .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                            .unwrap_or(0);
+                        if buf.len() >= i + 4 + len || k == 0 {
+                            break txt[i + 4..].to_string();
+                        }
+                    } else if k == 0 {
+                        break String::new();
+                    }
+                };
+                b.lock().unwrap().push(body);
+                let status = statuses[n.min(statuses.len() - 1)];
+                let _ = conn.write_all(format!("HTTP/1.1 {status} X\r\ncontent-length: 0\r\nconnection: close\r\n\r\n").as_bytes());
+            }
+        });
+        (url, bodies, hits)
+    }
+
+    fn ack(msg_id: &str) -> Ack {
+        Ack::new(Some(msg_id.to_string()), None, AckOutcome::Applied, None)
+    }
+
+    #[tokio::test]
+    async fn delivers_on_success() {
+        let (url, bodies, hits) = stand_in(vec![200]);
+        assert!(deliver(&reqwest::Client::new(), &url, &ack("m1")).await.is_ok());
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        let body: serde_json::Value = serde_json::from_str(&bodies.lock().unwrap()[0]).unwrap();
+        assert_eq!((body["msg_id"].as_str(), body["outcome"].as_str()), (Some("m1"), Some("applied")));
+    }
+
+    #[tokio::test]
+    async fn client_error_is_permanent_and_not_retried() {
+        let (url, _, hits) = stand_in(vec![400]);
+        let err = deliver(&reqwest::Client::new(), &url, &ack("m1")).await.unwrap_err();
+        assert!(err.permanent);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn server_error_and_429_are_retried() {
+        let (url, _, hits) = stand_in(vec![503, 429, 200]);
+        assert!(deliver(&reqwest::Client::new(), &url, &ack("m1")).await.is_ok());
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+
+        let (url, _, hits) = stand_in(vec![500]);
+        let err = deliver(&reqwest::Client::new(), &url, &ack("m1")).await.unwrap_err();
+        assert!(!err.permanent);
+        assert_eq!(hits.load(Ordering::SeqCst), MAX_ATTEMPTS as usize);
+    }
+
+    #[tokio::test]
+    async fn rejected_ack_does_not_block_the_queue() {
+        let (url, bodies, _) = stand_in(vec![422, 200, 200]);
+        let state = AppState::new(crate::builder::layout(crate::builder::column().child(crate::builder::text("t", "x"))));
+        state.config.lock().unwrap().ack.url = url;
+        state.acks.push(ack("malo"));
+        state.acks.push(ack("bueno"));
+        let worker = tokio::spawn(ack_worker(state.clone()));
+        for _ in 0..50 {
+            if state.acks.front().is_none() {
+                break;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+        worker.abort();
+        assert!(state.acks.front().is_none(), "la cola debería haberse vaciado");
+        assert_eq!(state.acks.inner.lock().unwrap().dropped, 1);
+        let bodies = bodies.lock().unwrap();
+        assert!(bodies[1].contains("\"bueno\""));
+    }
+}
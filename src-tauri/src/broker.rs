@@ -1,64 +1,25 @@
 use tauri::{AppHandle, Emitter};
-use crate::state::AppState;
+use crate::ack::{Ack, AckOutcome};
+use crate::commands::{apply_extracted, layout_or_style, CommandContext, CommandOutcome, Extracted};
+use crate::envelope::{Envelope, Rejection};
+use crate::signature::Keyring;
+use crate::config::BrokerConfig;
+use crate::curve::CurveKeys;
+use crate::redact;
+use crate::state::{AppState, BrokerConnState};
 use serde_json::{Value, json};
-
-const BROKER_ENDPOINT: &str = "tcp://34.70.157.148:5557";
-
-// ===== base64 (API moderna) =====
-use base64::Engine as _;
-use base64::engine::general_purpose::STANDARD;
-
-// --------------------- helpers parsing ---------------------
-fn parse_json_str(s: &str) -> Option<Value> {
-    serde_json::from_str::<Value>(s).ok()
-}
-fn parse_base64_json(s: &str) -> Option<Value> {
-    let bytes = STANDARD.decode(s).ok()?;
-    let txt = String::from_utf8(bytes).ok()?;
-    parse_json_str(&txt)
-}
+use chrono::Utc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
 
 fn looks_like_json(bytes: &[u8]) -> bool {
     let mut it = bytes.iter().skip_while(|c| c.is_ascii_whitespace());
     matches!(it.next(), Some(b'{') | Some(b'['))
 }
 
-// --------------------- style → layout ---------------------
-fn style_to_layout(style: &Value, screen_id: Option<&str>) -> Option<Value> {
-    let bg = style.get("background").and_then(|v| v.as_str()).unwrap_or("#129ADA");
-    let screens = style.get("screens")?.as_array()?;
-    let pick = if let Some(id) = screen_id {
-        screens.iter().find(|s| s.get("id").and_then(|v| v.as_str()) == Some(id))
-    } else {
-        screens.first()
-    }?.clone();
-
-    let children = pick.get("children").cloned().unwrap_or(json!([]));
-
-    let mut layout = json!({
-      "background": bg,
-      "root": {
-        "type": "column",
-        "background": bg,
-        "padding": 24,
-        "gap": 12,
-        "children": children
-      }
-    });
-
-    if let Some(cd) = style.get("customer_display") {
-        layout["customer_display"] = cd.clone();
-    }
-    if let Some(lg) = style.get("logo").and_then(|o| o.get("base64")).and_then(|v| v.as_str()) {
-        layout["__style_logo_base64"] = Value::String(lg.to_string());
-        layout["__style_logo_meta"] = style.get("logo").cloned().unwrap_or(json!({}));
-    }
-
-    Some(layout)
-}
-
-// --------------------- extrae layout de un Value ---------------------
-fn extract_layout_from_value(v: &Value) -> Option<Value> {
+// --------------------- extrae layout de un Value (frames sin cmd) ---------------------
+fn extract_layout_from_value(v: &Value) -> Option<Extracted> {
     // Debug útil
     if let Some(obj) = v.as_object() {
         let keys: Vec<&str> = obj.keys().map(|s| s.as_str()).collect();
@@ -66,223 +27,483 @@ fn extract_layout_from_value(v: &Value) -> Option<Value> {
         eprintln!("[ZMQ][dbg] keys={:?} cmd={}", keys, cmd_name);
     }
 
-    // 1) Layout directo
-    if v.get("root").is_some() {
-        return Some(v.clone());
+    // 1) Layout directo / 3) style suelto (frame sólo con screens)
+    if let Some(found) = layout_or_style(v) {
+        return Some(found);
     }
 
     // 2) Top-level content (layout o style)
-    if let Some(content) = v.get("content") {
-        if content.get("root").is_some() {
-            return Some(content.clone());
-        }
-        if content.get("screens").is_some() {
-            return style_to_layout(content, None);
-        }
+    if let Some(found) = v.get("content").and_then(layout_or_style) {
+        return Some(found);
     }
 
-    // 3) Top-level style suelto (frame sólo con screens)
-    if v.get("screens").is_some() {
-        return style_to_layout(v, None);
+    // 4) Envelope
+    v.get("envelope").and_then(|e| e.get("content")).and_then(layout_or_style)
+}
+
+// --------------------- principal: prueba TODOS los frames ---------------------
+fn emit_layout_update(app: &AppHandle, json: &str) {
+    let _ = app.emit("layout_update", json.to_string());
+}
+
+// nombre de comando para el ACK
+fn command_name(v: &Value) -> Option<String> {
+    v.get("cmd")?.get("name")?.as_str().map(str::to_string)
+}
+
+fn rejection_outcome(rejection: &Rejection) -> AckOutcome {
+    match rejection {
+        Rejection::Duplicate => AckOutcome::Duplicate,
+        Rejection::OutOfOrder { .. } => AckOutcome::OutOfOrder,
+        Rejection::Expired { .. } => AckOutcome::Expired,
     }
+}
 
-    // 4) Envelope
-    if let Some(env) = v.get("envelope") {
-        if let Some(content) = env.get("content") {
-            if content.get("root").is_some() {
-                return Some(content.clone());
-            }
-            if content.get("screens").is_some() {
-                return style_to_layout(content, None);
+// Cómo queda cada CommandOutcome en pantalla y en el ACK
+fn report(app: &AppHandle, state: &AppState, what: &str, outcome: CommandOutcome) -> Option<(AckOutcome, Option<String>)> {
+    match outcome {
+        CommandOutcome::Applied { relayout } => {
+            if relayout {
+                emit_layout_update(app, &state.get_layout());
             }
+            Some((AckOutcome::Applied, None))
+        }
+        // comandos que tocan el layout en sitio: si fallan no se aplicó nada y la pantalla sigue igual
+        CommandOutcome::Rejected(detail) => {
+            let detail = redact::text(&detail);
+            eprintln!("[ZMQ] {what} rechazado: {detail}");
+            Some((AckOutcome::Invalid, Some(detail)))
         }
+        CommandOutcome::LayoutRejected(detail) => {
+            let detail = redact::text(&detail);
+            eprintln!("[ZMQ] layout rechazado: {detail}");
+            state.restore_last_good();
+            emit_layout_update(app, &state.get_layout());
+            Some((AckOutcome::FallbackRestored, Some(detail)))
+        }
+        CommandOutcome::NotFound => None,
     }
+}
 
-    // 5) Comandos
-    if let Some(cmd) = v.get("cmd") {
-        let name = cmd.get("name").and_then(|n| n.as_str()).unwrap_or_default();
-        let args = cmd.get("args").cloned().unwrap_or(json!({}));
-
-        match name {
-            // ui.apply → args.content (root o style) o top-level content
-            "ui.apply" | "ui.update" => {
-                if let Some(content) = args.get("content") {
-                    if content.get("root").is_some() {
-                        return Some(content.clone());
-                    }
-                    if content.get("screens").is_some() {
-                        return style_to_layout(content, None);
-                    }
-                }
-                if let Some(content) = v.get("content") {
-                    if content.get("root").is_some() {
-                        return Some(content.clone());
-                    }
-                    if content.get("screens").is_some() {
-                        return style_to_layout(content, None);
-                    }
-                }
-            }
+// Para el log: enmascarar antes de cortar, un PAN cortado a la mitad ya no se reconoce
+fn frame_preview(bytes: &[u8]) -> String {
+    redact::text(&String::from_utf8_lossy(bytes)).chars().take(240).collect()
+}
 
-            // ui.style.apply → args.style / args.style_json / args.data_base64 / files[]
-            "ui.style.apply" | "ui.style.update" => {
-                // a) objeto style directo
-                if let Some(style) = args.get("style") {
-                    return style_to_layout(style, None);
-                }
-                // b) style en string JSON
-                if let Some(txt) = args.get("style_json").and_then(|v| v.as_str()) {
-                    if let Some(style) = parse_json_str(txt) {
-                        return style_to_layout(&style, None);
-                    }
-                }
-                // c) style en base64 (TU CASO)
-                if let Some(b64) = args.get("data_base64").and_then(|v| v.as_str()) {
-                    if let Some(style) = parse_base64_json(b64) {
-                        return style_to_layout(&style, None);
-                    }
-                }
-                // d) style referenciado en files (content/text/base64)
-                if let Some(style) = find_style_in_files(v) {
-                    return style_to_layout(&style, None);
+// Qué hizo el primer frame que trajo algo aplicable (sin AppHandle, para poder probarlo)
+enum Resolved {
+    Handled { what: String, outcome: CommandOutcome },
+    Unknown(String),
+}
+
+// Aplica el primer frame con layout/comando; el resto del mensaje se ignora
+// `source` (msg_id o "broker") queda en el historial de layouts
+fn resolve_values(state: &AppState, values: &[Value], source: &str) -> Option<Resolved> {
+    let mut unknown: Option<String> = None;
+
+    // intenta con TODOS los frames JSON
+    for v in values {
+        if let Some(name) = command_name(v) {
+            let args = v.get("cmd").and_then(|c| c.get("args")).cloned().unwrap_or(json!({}));
+            let ctx = CommandContext { state, message: v, source };
+            match state.commands.dispatch(&name, args, &ctx) {
+                // el comando no encontró su contenido: igual que un cmd desconocido, se busca layout/content
+                Some(CommandOutcome::NotFound) => {}
+                Some(outcome) => return Some(Resolved::Handled { what: name, outcome }),
+                // compatibilidad: un frame con cmd desconocido pero con layout/content se sigue aplicando
+                None => {
+                    eprintln!("[ZMQ] ⚠️ comando desconocido '{name}' (registrados: {:?})", state.commands.names());
+                    unknown = Some(name);
                 }
             }
-            _ => {}
+        }
+        if let Some(found) = extract_layout_from_value(v) {
+            let outcome = apply_extracted(state, found, source);
+            return Some(Resolved::Handled { what: "layout".to_string(), outcome });
         }
     }
 
-    None
+    unknown.map(Resolved::Unknown)
 }
 
-// Busca un objeto style en files[] (content/text/base64)
-fn find_style_in_files(root: &Value) -> Option<Value> {
-    let files = root.get("files")?.as_array()?;
-    let mut candidates: Vec<&Value> = files.iter().collect();
-    candidates.sort_by_key(|f| {
-        let name = f.get("name").and_then(|v| v.as_str()).unwrap_or("");
-        let score = if name.to_ascii_lowercase().contains("style") { 0 } else { 1 };
-        (score, name.len())
-    });
-
-    for f in candidates {
-        if let Some(c) = f.get("content") {
-            if c.get("screens").is_some() { return Some(c.clone()); }
-            if let Some(txt) = c.as_str() {
-                if let Some(v) = parse_json_str(txt) {
-                    if v.get("screens").is_some() { return Some(v); }
-                }
-            }
+fn process_values(
+    app: &AppHandle,
+    state: &AppState,
+    values: &[Value],
+    source: &str,
+) -> Option<(AckOutcome, Option<String>)> {
+    match resolve_values(state, values, source)? {
+        Resolved::Handled { what, outcome } => report(app, state, &what, outcome),
+        Resolved::Unknown(name) => Some(unknown_command(&name)),
+    }
+}
+
+// Un cmd sin handler (y sin layout aplicable) igual se contesta: el broker se entera por el ACK
+fn unknown_command(name: &str) -> (AckOutcome, Option<String>) {
+    (AckOutcome::UnknownCommand, Some(format!("comando desconocido: {name}")))
+}
+
+// Procesa un mensaje multipart completo y deja su ACK en la cola
+pub fn handle_frames(app: &AppHandle, state: &AppState, keyring: &Keyring, frames: &[Vec<u8>]) {
+    let mut values = Vec::new();
+    let mut unreadable: Option<String> = None;
+    for bytes in frames.iter().filter(|b| looks_like_json(b)) {
+        match std::str::from_utf8(bytes) {
+            Ok(txt) => match serde_json::from_str::<Value>(txt) {
+                Ok(v) => values.push(v),
+                Err(e) => unreadable = Some(format!("JSON inválido: {e}")),
+            },
+            Err(_) => unreadable = Some("frame JSON no UTF-8".to_string()),
         }
-        if let Some(txt) = f.get("text").and_then(|v| v.as_str()) {
-            if let Some(v) = parse_json_str(txt) {
-                if v.get("screens").is_some() { return Some(v); }
-            }
+    }
+
+    let env = values.last().map(Envelope::from_value).unwrap_or_default();
+    let command = values.last().and_then(command_name);
+
+    // 🔐 cada frame JSON tiene que verificar; lo no autenticado ni siquiera pasa por el dedup
+    if let Err(e) = values.iter().try_for_each(|v| keyring.verify(v).map(|_| ())) {
+        eprintln!("[ZMQ] 🔐 mensaje rechazado (msg_id={:?} source={}): {e}", env.msg_id, env.source_key());
+        state.acks.push(Ack::new(env.msg_id, command, AckOutcome::Unauthenticated, Some(e)));
+        return;
+    }
+    let admitted = state.gate.lock().unwrap().admit(&env, Utc::now().timestamp_millis());
+
+    let source = env.msg_id.as_deref().unwrap_or("broker");
+    let processed = match admitted {
+        Ok(()) => process_values(app, state, &values, source),
+        Err(rejection) => {
+            eprintln!(
+                "[ZMQ] ⏭️ mensaje descartado (msg_id={:?} seq={:?} source={}): {rejection}",
+                env.msg_id,
+                env.seq,
+                env.source_key()
+            );
+            Some((rejection_outcome(&rejection), Some(rejection.to_string())))
         }
-        for key in &["content_b64", "base64", "bytes_b64"] {
-            if let Some(b64) = f.get(*key).and_then(|v| v.as_str()) {
-                if let Some(v) = parse_base64_json(b64) {
-                    if v.get("screens").is_some() { return Some(v); }
-                }
+    };
+    let (outcome, error) = match processed {
+        Some(result) => result,
+        None => {
+            if let Some(bytes) = frames.iter().rev().find(|b| looks_like_json(b)) {
+                eprintln!("[ZMQ] ❌ sin layout (tras revisar todos los frames). preview: {}", frame_preview(bytes));
+            } else {
+                eprintln!("[ZMQ] ❌ sin layout (no hubo frames JSON).");
+            }
+            match unreadable {
+                Some(e) => (AckOutcome::Invalid, Some(e)),
+                None => (AckOutcome::NoLayoutFound, None),
             }
         }
+    };
+
+    state.acks.push(Ack::new(env.msg_id, command, outcome, error));
+}
+
+// --------------------- listener supervisado ---------------------
+const POLL_MS: i64 = 1_000;
+const BACKOFF_MIN_MS: u64 = 500;
+const BACKOFF_MAX_MS: u64 = 30_000;
+static MONITOR_SEQ: AtomicU64 = AtomicU64::new(0); // un inproc distinto por sesión
+
+fn set_conn(app: &AppHandle, state: &AppState, endpoint: &str, conn: BrokerConnState, err: Option<String>, retries: u32) {
+    if let Some(status) = state.set_broker_state(endpoint, conn, err, retries) {
+        eprintln!("[ZMQ] estado → {:?} ({endpoint})", status.state);
+        let _ = app.emit("broker_status", status);
     }
-    None
 }
 
-// --------------------- principal: prueba TODOS los frames ---------------------
-fn emit_layout_update(app: &AppHandle, json: &str) {
-    let _ = app.emit("layout_update", json.to_string());
+// Primer frame del monitor: u16 evento + u32 valor; el segundo trae el endpoint
+fn read_monitor_event(monitor: &zmq::Socket) -> Option<u16> {
+    let frames = monitor.recv_multipart(zmq::DONTWAIT).ok()?;
+    let head = frames.first()?;
+    (head.len() >= 2).then(|| u16::from_ne_bytes([head[0], head[1]]))
 }
 
-pub fn start_zmq_listener(app: AppHandle, state: AppState) {
-    std::thread::spawn(move || {
-        let endpoint = std::env::var("TAURI_ZMQ_SUB").unwrap_or_else(|_| BROKER_ENDPOINT.to_string());
+// Una sesión = un socket SUB vivo. Devuelve Err cuando hay que recrearlo
+// y Ok cuando se pidió stop / reload (el socket se cierra al salir).
+fn run_session(
+    app: &AppHandle,
+    state: &AppState,
+    ctx: &zmq::Context,
+    keyring: &Keyring,
+    cfg: &BrokerConfig,
+    signals: &Signals,
+    retries: &mut u32,
+) -> Result<(), String> {
+    let endpoint = cfg.sub_endpoint.as_str();
+    let stall_after_ms = cfg.stall_after_secs as i64 * 1000; // conectado pero sin frames → stalled
+    let reset_after_ms = cfg.reset_after_secs as i64 * 1000; // sin conexión tanto tiempo → recrear el socket
+    let socket = ctx.socket(zmq::SUB).map_err(|e| format!("no se pudo crear SUB: {e}"))?;
+    socket.set_subscribe(b"").map_err(|e| format!("no se pudo suscribir: {e}"))?;
+    socket.set_linger(0).map_err(|e| e.to_string())?;
+    // heartbeats ZMTP: si el broker deja de contestar, zmq corta y reconecta solo
+    socket.set_heartbeat_ivl(5_000).map_err(|e| e.to_string())?;
+    socket.set_heartbeat_timeout(15_000).map_err(|e| e.to_string())?;
+    socket.set_reconnect_ivl(BACKOFF_MIN_MS as i32).map_err(|e| e.to_string())?;
+    socket.set_reconnect_ivl_max(BACKOFF_MAX_MS as i32).map_err(|e| e.to_string())?;
+    // 🔐 CURVE si hay llaves configuradas; si están mal no se conecta en claro
+    let curve = CurveKeys::load(cfg.curve_keys_file.as_deref())?;
+    if let Some(keys) = &curve {
+        keys.apply(&socket)?;
+    }
 
-        let ctx = zmq::Context::new();
-        let socket = ctx.socket(zmq::SUB).expect("[ZMQ] no se pudo crear SUB");
-        socket.set_subscribe(b"").expect("[ZMQ] no se pudo suscribir");
-        socket.connect(&endpoint).expect("[ZMQ] no se pudo conectar al broker");
+    let session = MONITOR_SEQ.fetch_add(1, Ordering::Relaxed);
+    let monitor_ep = format!("inproc://broker-monitor-{session}");
+    socket
+        .monitor(&monitor_ep, zmq::SocketEvent::ALL as i32)
+        .map_err(|e| format!("no se pudo monitorear SUB: {e}"))?;
+    let monitor = ctx.socket(zmq::PAIR).map_err(|e| e.to_string())?;
+    monitor.set_linger(0).map_err(|e| e.to_string())?;
+    monitor.connect(&monitor_ep).map_err(|e| e.to_string())?;
 
-        eprintln!("[ZMQ] SUB conectado a {endpoint}");
+    socket.connect(endpoint).map_err(|e| format!("no se pudo conectar a {endpoint}: {e}"))?;
+    set_conn(app, state, endpoint, BrokerConnState::Connecting, None, *retries);
+    eprintln!("[ZMQ] SUB conectando a {endpoint}{}", if curve.is_some() { " (CURVE)" } else { "" });
 
-        loop {
-            let frames = match socket.recv_multipart(0) {
-                Ok(f) => f,
-                Err(e) => {
-                    eprintln!("[ZMQ] error recibiendo: {e:?}");
-                    std::thread::sleep(std::time::Duration::from_millis(500));
-                    continue;
-                }
-            };
-
-            let mut applied = false;
-
-            // 1) intenta con TODOS los frames JSON
-            for bytes in frames.iter().filter(|b| looks_like_json(b)) {
-                if let Ok(txt) = String::from_utf8(bytes.clone()) {
-                    if let Some(v) = parse_json_str(&txt) {
-                        if let Some(layout_v) = extract_layout_from_value(&v) {
-                            if let Ok(layout_json) = serde_json::to_string(&layout_v) {
-                                if state.apply_layout_safely(&layout_json) {
-                                    emit_layout_update(&app, &layout_json);
-                                    applied = true;
-                                    break;
-                                } else {
-                                    state.restore_last_good();
-                                    let fallback = state.get_layout();
-                                    emit_layout_update(&app, &fallback);
-                                    applied = true;
-                                    break;
-                                }
-                            }
-                        }
-                    }
+    let mut connected = false;
+    let mut ever_connected = false; // tras una caída seguimos en stalled, no en connecting
+    let mut last_activity = Utc::now().timestamp_millis();
+
+    while !signals.pending() {
+        let mut items = [
+            socket.as_poll_item(zmq::POLLIN),
+            monitor.as_poll_item(zmq::POLLIN),
+        ];
+        zmq::poll(&mut items, POLL_MS).map_err(|e| format!("poll: {e}"))?;
+        let now = Utc::now().timestamp_millis();
+
+        if items[1].is_readable() {
+            while let Some(ev) = read_monitor_event(&monitor) {
+                if ev == zmq::SocketEvent::CONNECTED.to_raw() || ev == zmq::SocketEvent::HANDSHAKE_SUCCEEDED.to_raw() {
+                    connected = true;
+                    ever_connected = true;
+                    *retries = 0;
+                    last_activity = now;
+                    set_conn(app, state, endpoint, BrokerConnState::Connected, None, 0);
+                } else if ev == zmq::SocketEvent::DISCONNECTED.to_raw() {
+                    connected = false;
+                    last_activity = now;
+                    set_conn(app, state, endpoint, BrokerConnState::Stalled, Some("desconectado; reintentando".into()), *retries);
+                } else if ev == zmq::SocketEvent::CONNECT_RETRIED.to_raw() && !ever_connected {
+                    set_conn(app, state, endpoint, BrokerConnState::Connecting, None, *retries);
+                } else if ev == zmq::SocketEvent::HANDSHAKE_FAILED_AUTH.to_raw() {
+                    set_conn(app, state, endpoint, BrokerConnState::Stalled, Some("el broker rechazó la autenticación CURVE".into()), *retries);
+                } else if ev == zmq::SocketEvent::HANDSHAKE_FAILED_NO_DETAIL.to_raw()
+                    || ev == zmq::SocketEvent::HANDSHAKE_FAILED_PROTOCOL.to_raw()
+                {
+                    set_conn(app, state, endpoint, BrokerConnState::Stalled, Some("handshake fallido".into()), *retries);
                 }
             }
+        }
 
-            // 2) si no aplicó, aún puede que el *style* venga DENTRO del envelope como base64
-            if !applied {
-                if let Some(last_json_bytes) = frames.iter().rev().find(|b| looks_like_json(b)) {
-                    if let Ok(txt) = String::from_utf8(last_json_bytes.clone()) {
-                        if let Some(v) = parse_json_str(&txt) {
-                            // intenta leer args.data_base64 si está
-                            if let Some(cmd) = v.get("cmd") {
-                                let args = cmd.get("args").cloned().unwrap_or(json!({}));
-                                if let Some(b64) = args.get("data_base64").and_then(|x| x.as_str()) {
-                                    if let Some(style) = parse_base64_json(b64) {
-                                        if let Some(layout_v) = style_to_layout(&style, None) {
-                                            if let Ok(layout_json) = serde_json::to_string(&layout_v) {
-                                                if state.apply_layout_safely(&layout_json) {
-                                                    emit_layout_update(&app, &layout_json);
-                                                    applied = true;
-                                                } else {
-                                                    state.restore_last_good();
-                                                    let fallback = state.get_layout();
-                                                    emit_layout_update(&app, &fallback);
-                                                    applied = true;
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+        if items[0].is_readable() {
+            let frames = socket.recv_multipart(zmq::DONTWAIT).map_err(|e| format!("error recibiendo: {e}"))?;
+            last_activity = now;
+            state.mark_message_received();
+            if connected {
+                set_conn(app, state, endpoint, BrokerConnState::Connected, None, 0);
             }
+            handle_frames(app, state, keyring, &frames);
+        }
 
-            if !applied {
-                if let Some(bytes) = frames.iter().rev().find(|b| looks_like_json(b)) {
-                    if let Ok(txt) = String::from_utf8(bytes.clone()) {
-                        let preview = if txt.len() > 240 { &txt[..240] } else { &txt };
-                        eprintln!("[ZMQ] ❌ sin layout (tras revisar todos los frames). preview: {}", preview);
-                    } else {
-                        eprintln!("[ZMQ] ❌ sin layout (frame JSON no UTF-8).");
-                    }
-                } else {
-                    eprintln!("[ZMQ] ❌ sin layout (no hubo frames JSON).");
-                }
+        let idle = now - last_activity;
+        if connected && idle > stall_after_ms {
+            // mensaje fijo: desde cuándo está trabado ya lo dice since_millis (y así no se re-emite cada poll)
+            let err = format!("sin frames hace más de {}s", stall_after_ms / 1000);
+            set_conn(app, state, endpoint, BrokerConnState::Stalled, Some(err), *retries);
+        }
+        if !connected && idle > reset_after_ms {
+            return Err(format!("sin conexión hace {}s", idle / 1000));
+        }
+    }
+    let _ = socket.disconnect(endpoint);
+    Ok(())
+}
+
+// Señales hacia el hilo del listener (se revisan en cada poll y durante el backoff)
+#[derive(Default)]
+struct Signals {
+    stop: AtomicBool,   // terminar el hilo
+    reload: AtomicBool, // cerrar la sesión y abrir otra con la config actual
+}
+
+impl Signals {
+    fn pending(&self) -> bool {
+        self.stop.load(Ordering::SeqCst) || self.reload.load(Ordering::SeqCst)
+    }
+}
+
+struct Running {
+    signals: Arc<Signals>,
+    thread: JoinHandle<()>,
+}
+
+// Handle del listener que vive en AppState: broker_start / broker_stop / broker_set_endpoint
+#[derive(Clone, Default)]
+pub struct ListenerHandle {
+    running: Arc<Mutex<Option<Running>>>,
+}
+
+impl ListenerHandle {
+    pub fn is_running(&self) -> bool {
+        self.running.lock().unwrap().as_ref().is_some_and(|r| !r.thread.is_finished())
+    }
+
+    // La sesión actual se cierra y la siguiente lee la config de nuevo (sin backoff)
+    pub fn request_reload(&self) -> bool {
+        match self.running.lock().unwrap().as_ref() {
+            Some(r) => {
+                r.signals.reload.store(true, Ordering::SeqCst);
+                true
             }
+            None => false,
+        }
+    }
+}
+
+pub fn start_zmq_listener(app: AppHandle, state: AppState) -> Result<(), String> {
+    let handle = state.listener.clone();
+    let mut running = handle.running.lock().unwrap();
+    if running.as_ref().is_some_and(|r| !r.thread.is_finished()) {
+        return Err("el listener ya está corriendo".to_string());
+    }
+
+    let signals = Arc::new(Signals::default());
+    let thread_signals = signals.clone();
+    let thread = std::thread::Builder::new()
+        .name("zmq-listener".into())
+        .spawn(move || listener_loop(app, state, &thread_signals))
+        .map_err(|e| format!("no se pudo crear el hilo del listener: {e}"))?;
+    *running = Some(Running { signals, thread });
+    Ok(())
+}
+
+// Cierra el socket de forma ordenada y espera al hilo (salvo que lo pida el propio
+// listener, p.ej. desde un comando del broker: ahí sólo se marca y termina solo)
+pub fn stop_zmq_listener(state: &AppState) -> Result<(), String> {
+    let Some(running) = state.listener.running.lock().unwrap().take() else {
+        return Err("el listener no está corriendo".to_string());
+    };
+    running.signals.stop.store(true, Ordering::SeqCst);
+    if running.thread.thread().id() != std::thread::current().id() {
+        running.thread.join().map_err(|_| "el hilo del listener terminó con panic".to_string())?;
+    }
+    Ok(())
+}
+
+// Espera `ms` pero sale antes si llega stop / reload
+fn interruptible_sleep(signals: &Signals, ms: u64) {
+    let deadline = std::time::Instant::now() + std::time::Duration::from_millis(ms);
+    while !signals.pending() && std::time::Instant::now() < deadline {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+}
+
+fn listener_loop(app: AppHandle, state: AppState, signals: &Signals) {
+    let ctx = zmq::Context::new();
+    let mut retries: u32 = 0;
+    loop {
+        // cada sesión relee la config: broker_set_endpoint / config.update aplican aquí
+        let cfg = state.config().broker;
+        let keyring = Keyring::load(cfg.keys_file.as_deref(), cfg.require_signed);
+        let result = run_session(&app, &state, &ctx, &keyring, &cfg, signals, &mut retries);
+
+        if signals.stop.load(Ordering::SeqCst) {
+            eprintln!("[ZMQ] listener detenido");
+            set_conn(&app, &state, &cfg.sub_endpoint, BrokerConnState::Down, Some("detenido".into()), 0);
+            return;
         }
-    });
+        if signals.reload.swap(false, Ordering::SeqCst) {
+            eprintln!("[ZMQ] reiniciando sesión con la config nueva");
+            retries = 0;
+            continue;
+        }
+
+        let err = result.err().unwrap_or_else(|| "sesión terminada".to_string());
+        retries = retries.saturating_add(1);
+        // backoff exponencial: 0.5s, 1s, 2s ... hasta 30s
+        let wait = BACKOFF_MIN_MS
+            .saturating_mul(1u64 << retries.saturating_sub(1).min(16))
+            .min(BACKOFF_MAX_MS);
+        eprintln!("[ZMQ] ❌ {err}; reintento #{retries} en {wait}ms");
+        set_conn(&app, &state, &cfg.sub_endpoint, BrokerConnState::Down, Some(err), retries);
+        interruptible_sleep(signals, wait);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{column, layout, text};
+
+    fn state() -> AppState {
+        AppState::new(layout(column().child(text("t", "inicio"))))
+    }
+
+    fn layout_json(label: &str) -> Value {
+        json!({ "root": { "type": "column", "children": [{ "type": "text", "id": "t", "text": label }] } })
+    }
+
+    fn shows(st: &AppState, label: &str) -> bool {
+        st.get_layout().contains(label)
+    }
+
+    fn applied(resolved: Option<Resolved>) -> bool {
+        matches!(resolved, Some(Resolved::Handled { outcome: CommandOutcome::Applied { .. }, .. }))
+    }
+
+    #[test]
+    fn known_command_without_content_falls_back_to_envelope_content() {
+        let st = state();
+        let msg = json!({ "cmd": { "name": "ui.apply", "args": {} }, "envelope": { "content": layout_json("del envelope") } });
+        assert!(applied(resolve_values(&st, &[msg], "m1")));
+        assert!(shows(&st, "del envelope"));
+    }
+
+    #[test]
+    fn known_command_without_content_tries_the_next_frame() {
+        let st = state();
+        let frames = [json!({ "cmd": { "name": "ui.style.apply", "args": {} } }), layout_json("segundo frame")];
+        assert!(applied(resolve_values(&st, &frames, "m1")));
+        assert!(shows(&st, "segundo frame"));
+    }
+
+    #[test]
+    fn unknown_command_still_applies_content() {
+        let st = state();
+        let msg = json!({ "cmd": { "name": "ui.nuevo" }, "content": layout_json("contenido") });
+        assert!(applied(resolve_values(&st, &[msg], "m1")));
+        assert!(shows(&st, "contenido"));
+        let bare = json!({ "cmd": { "name": "ui.nuevo" } });
+        assert!(matches!(resolve_values(&st, &[bare], "m2"), Some(Resolved::Unknown(n)) if n == "ui.nuevo"));
+    }
+
+    #[test]
+    fn unknown_command_is_acked() {
+        let st = state();
+        let Some(Resolved::Unknown(name)) = resolve_values(&st, &[json!({ "cmd": { "name": "ui.nuevo" } })], "m1") else {
+            panic!("se esperaba Unknown");
+        };
+        assert_eq!(
+            unknown_command(&name),
+            (AckOutcome::UnknownCommand, Some("comando desconocido: ui.nuevo".to_string()))
+        );
+        assert!(shows(&st, "inicio"));
+    }
+
+    #[test]
+    fn preview_masks_before_cutting() {
+        // el PAN cae justo en el corte de 240 caracteres
+        let frame = format!(r#"{{"pad":"{}","track2":";4111111111111111=25121010000000000000?"}}"#, "x".repeat(205));
+        let preview = frame_preview(frame.as_bytes());
+        assert_eq!(preview.chars().count(), 240);
+        assert!(preview.ends_with(";411111******11") && !preview.contains("41111111"), "{preview}");
+    }
+
+    #[test]
+    fn nothing_applicable_is_none() {
+        let st = state();
+        assert!(resolve_values(&st, &[json!({ "cmd": { "name": "ui.apply" } })], "m1").is_none());
+        assert!(shows(&st, "inicio"));
+    }
 }
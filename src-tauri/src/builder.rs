@@ -0,0 +1,333 @@
+// Builder fluido para armar pantallas desde Rust.
+// Todo pasa por los tipos de layout.rs y se serializa con serde,
+// así que no hay que escapar nada a mano (comillas, \n, datos crudos de MSR...).
+//
+//   layout(column().padding(24).child(text("txt_title", "Inicio").bold()))
+
+use crate::layout::{
+    Align, BaseNode, ButtonNode, ColumnNode, CustomerDisplay, InputMoneyNode, InputPasswordNode,
+    InputTextNode, LogoNode, ScrollTextNode, SpacerNode, TextNode, UiLayout, UiNode,
+};
+
+// --------------------- constructores ---------------------
+pub fn layout(root: impl Into<UiNode>) -> UiLayout {
+    UiLayout {
+        background: None,
+        root: root.into(),
+        customer_display: None,
+        style_logo_base64: None,
+        style_logo_meta: None,
+    }
+}
+
+pub fn column() -> ColumnNode {
+    ColumnNode::default()
+}
+
+pub fn text(id: &str, text: impl Into<String>) -> TextNode {
+    TextNode { text: text.into(), ..Default::default() }.id(id)
+}
+
+pub fn button(id: &str, text: impl Into<String>) -> ButtonNode {
+    ButtonNode { text: text.into(), ..Default::default() }.id(id)
+}
+
+pub fn spacer(id: &str, height: u32) -> SpacerNode {
+    SpacerNode { height: Some(height), ..Default::default() }.id(id)
+}
+
+pub fn scroll(id: &str, text: impl Into<String>) -> ScrollTextNode {
+    ScrollTextNode { text: text.into(), ..Default::default() }.id(id)
+}
+
+pub fn logo(id: &str) -> LogoNode {
+    LogoNode::default().id(id)
+}
+
+pub fn input_money(id: &str) -> InputMoneyNode {
+    InputMoneyNode::default().id(id)
+}
+
+pub fn input_text(id: &str) -> InputTextNode {
+    InputTextNode::default().id(id)
+}
+
+pub fn input_password(id: &str) -> InputPasswordNode {
+    InputPasswordNode::default().id(id)
+}
+
+pub fn customer_display(text: impl Into<String>) -> CustomerDisplay {
+    CustomerDisplay { text: Some(text.into()), ..Default::default() }
+}
+
+// --------------------- campos comunes (BaseNode) ---------------------
+pub trait NodeBuilder: Sized {
+    fn base_mut(&mut self) -> &mut BaseNode;
+
+    fn id(mut self, id: &str) -> Self {
+        self.base_mut().id = Some(id.to_string());
+        self
+    }
+    fn align(mut self, align: Align) -> Self {
+        self.base_mut().align = Some(align);
+        self
+    }
+    fn visible_when(mut self, flag: &str, expected: bool) -> Self {
+        let base = self.base_mut();
+        base.visible_when_flag = Some(flag.to_string());
+        base.visible_when = Some(expected);
+        self
+    }
+}
+
+macro_rules! node_builder {
+    ($($node:ident => $variant:ident),* $(,)?) => {
+        $(
+            impl NodeBuilder for $node {
+                fn base_mut(&mut self) -> &mut BaseNode {
+                    &mut self.base
+                }
+            }
+            impl From<$node> for UiNode {
+                fn from(n: $node) -> Self {
+                    UiNode::$variant(n)
+                }
+            }
+        )*
+    };
+}
+
+node_builder! {
+    TextNode => Text,
+    ButtonNode => Button,
+    SpacerNode => Spacer,
+    ScrollTextNode => Scroll,
+    ColumnNode => Column,
+    LogoNode => Logo,
+    InputMoneyNode => InputMoney,
+    InputTextNode => InputText,
+    InputPasswordNode => InputPassword,
+}
+
+// --------------------- por tipo de nodo ---------------------
+impl UiLayout {
+    pub fn background(mut self, color: &str) -> Self {
+        self.background = Some(color.to_string());
+        self
+    }
+    pub fn customer_display(mut self, cd: CustomerDisplay) -> Self {
+        self.customer_display = Some(cd);
+        self
+    }
+}
+
+impl CustomerDisplay {
+    pub fn size(mut self, size: u32) -> Self {
+        self.size = Some(size);
+        self
+    }
+    pub fn align(mut self, align: Align) -> Self {
+        self.align = Some(align);
+        self
+    }
+    pub fn use_logo(mut self, use_logo: bool) -> Self {
+        self.use_logo = Some(use_logo);
+        self
+    }
+    pub fn bg_color(mut self, color: &str) -> Self {
+        self.bg_color = Some(color.to_string());
+        self
+    }
+}
+
+impl ColumnNode {
+    pub fn background(mut self, color: &str) -> Self {
+        self.background = Some(color.to_string());
+        self
+    }
+    pub fn padding(mut self, padding: u32) -> Self {
+        self.padding = Some(padding);
+        self
+    }
+    pub fn gap(mut self, gap: u32) -> Self {
+        self.gap = Some(gap);
+        self
+    }
+    pub fn child(mut self, node: impl Into<UiNode>) -> Self {
+        self.children.push(node.into());
+        self
+    }
+}
+
+impl TextNode {
+    pub fn size(mut self, size: u32) -> Self {
+        self.size = Some(size);
+        self
+    }
+    pub fn bold(mut self) -> Self {
+        self.bold = Some(true);
+        self
+    }
+    pub fn color(mut self, color: &str) -> Self {
+        self.color = Some(color.to_string());
+        self
+    }
+}
+
+impl ButtonNode {
+    pub fn on_click(mut self, event_id: &str) -> Self {
+        self.on_click = Some(event_id.to_string());
+        self
+    }
+    pub fn tint(mut self, color: &str) -> Self {
+        self.tint = Some(color.to_string());
+        self
+    }
+    pub fn text_color(mut self, color: &str) -> Self {
+        self.text_color = Some(color.to_string());
+        self
+    }
+    pub fn icon(mut self, icon: &str) -> Self {
+        self.icon = Some(icon.to_string());
+        self
+    }
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = Some(enabled);
+        self
+    }
+}
+
+impl ScrollTextNode {
+    pub fn padding(mut self, padding: u32) -> Self {
+        self.padding = Some(padding);
+        self
+    }
+    pub fn weight(mut self, weight: f32) -> Self {
+        self.weight = Some(weight);
+        self
+    }
+    pub fn color(mut self, color: &str) -> Self {
+        self.color = Some(color.to_string());
+        self
+    }
+}
+
+impl LogoNode {
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.width = Some(width);
+        self.height = Some(height);
+        self
+    }
+}
+
+impl InputMoneyNode {
+    pub fn hint(mut self, hint: &str) -> Self {
+        self.hint = Some(hint.to_string());
+        self
+    }
+    pub fn currency(mut self, currency: &str) -> Self {
+        self.currency = Some(currency.to_string());
+        self
+    }
+}
+
+impl InputTextNode {
+    pub fn hint(mut self, hint: &str) -> Self {
+        self.hint = Some(hint.to_string());
+        self
+    }
+}
+
+impl InputPasswordNode {
+    pub fn hint(mut self, hint: &str) -> Self {
+        self.hint = Some(hint.to_string());
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn to_json(node: impl Into<UiNode>) -> serde_json::Value {
+        serde_json::to_value(node.into()).unwrap()
+    }
+
+    #[test]
+    fn constructors_set_id_and_type() {
+        let nodes: [(UiNode, &str); 8] = [
+            (text("a", "x").into(), "text"),
+            (button("b", "x").into(), "button"),
+            (spacer("c", 8).into(), "spacer"),
+            (scroll("d", "x").into(), "scroll"),
+            (logo("e").into(), "logo"),
+            (input_money("f").into(), "input_money"),
+            (input_text("g").into(), "input_text"),
+            (input_password("h").into(), "input_password"),
+        ];
+        for (node, kind) in nodes {
+            assert_eq!(node.type_name(), kind);
+            assert_eq!(to_json(node.clone())["type"], kind);
+            assert!(node.id().is_some_and(|id| id.len() == 1), "{kind}");
+        }
+        assert_eq!(UiNode::from(column()).id(), None);
+        assert_eq!(UiNode::from(column().id("col")).id(), Some("col"));
+    }
+
+    #[test]
+    fn children_keep_order_and_nesting() {
+        let root = column().child(text("t1", "uno")).child(column().id("inner").child(button("b1", "dos"))).child(spacer("s1", 4));
+        let json = to_json(root);
+        let ids: Vec<_> = json["children"].as_array().unwrap().iter().map(|c| c["id"].clone()).collect();
+        assert_eq!(ids, [json!("t1"), json!("inner"), json!("s1")]);
+        assert_eq!(json["children"][1]["children"][0], json!({ "type": "button", "id": "b1", "text": "dos" }));
+    }
+
+    #[test]
+    fn style_fields_land_where_the_front_reads_them() {
+        let b = button("b", "Ok").on_click("go").tint("#111").text_color("#fff").icon("print").enabled(false).align(Align::Center);
+        assert_eq!(
+            to_json(b),
+            json!({ "type": "button", "id": "b", "text": "Ok", "on_click": "go", "tint": "#111", "text_color": "#fff",
+                    "icon": "print", "enabled": false, "align": "center" })
+        );
+        assert_eq!(
+            to_json(text("t", "x").size(14).bold().color("#000")),
+            json!({ "type": "text", "id": "t", "text": "x", "size": 14, "bold": true, "color": "#000" })
+        );
+        assert_eq!(
+            to_json(column().background("#fff").padding(24).gap(8).child(logo("l").size(120, 40))),
+            json!({ "type": "column", "background": "#fff", "padding": 24, "gap": 8,
+                    "children": [{ "type": "logo", "id": "l", "width": 120, "height": 40 }] })
+        );
+        assert_eq!(
+            to_json(input_money("m").hint("Monto").currency("MXN")),
+            json!({ "type": "input_money", "id": "m", "hint": "Monto", "currency": "MXN" })
+        );
+    }
+
+    #[test]
+    fn unset_fields_are_not_serialized() {
+        let json = serde_json::to_value(UiNode::from(button("b", "Ok"))).unwrap();
+        assert_eq!(json, json!({ "type": "button", "id": "b", "text": "Ok" }));
+        let json = serde_json::to_value(UiNode::from(text("t", "x").visible_when("screen_start", true))).unwrap();
+        assert_eq!(json, json!({ "type": "text", "id": "t", "text": "x", "visible_when_flag": "screen_start", "visible_when": true }));
+
+        let bare = serde_json::to_value(layout(column().child(text("t", "x")))).unwrap();
+        assert_eq!(bare.as_object().unwrap().keys().collect::<Vec<_>>(), ["root"]);
+        let display = customer_display("Hola").size(24).use_logo(true).bg_color("#000");
+        let full = serde_json::to_value(layout(column().child(text("t", "x"))).background("#fff").customer_display(display)).unwrap();
+        assert_eq!(full["background"], "#fff");
+        assert_eq!(full["customer_display"], json!({ "text": "Hola", "size": 24, "use_logo": true, "bg_color": "#000" }));
+    }
+
+    #[test]
+    fn raw_text_survives_the_round_trip() {
+        // una banda cruda con '\' o un salto de línea no rompe el JSON
+        let raw = "%B4111^DOE\\JOHN^25?\nsegunda \"línea\"";
+        let built = layout(column().child(scroll("msr_result", raw)));
+        let back: UiLayout = serde_json::from_str(&built.to_json()).unwrap();
+        assert_eq!(back, built);
+    }
+}
@@ -0,0 +1,10 @@
+// FNV-1a de 64 bits: estable entre ejecuciones y plataformas (a diferencia de DefaultHasher).
+// No es criptográfico; sirve para detectar cambios y archivos corruptos.
+pub fn fnv1a64_hex(data: &[u8]) -> String {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in data {
+        h ^= u64::from(*b);
+        h = h.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("{h:016x}")
+}
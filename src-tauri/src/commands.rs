@@ -0,0 +1,403 @@
+// Comandos del broker ({ "cmd": { "name": "...", "args": {...} } }) despachados por nombre.
+// Cada handler declara sus args (se deserializan antes de llamarlo) y devuelve un
+// CommandOutcome; broker.rs se encarga de re-emitir el layout y del ACK.
+//
+// Para agregar un comando: implementar CommandHandler y registrarlo en
+// CommandRegistry::with_builtins (o en caliente con state.commands.register).
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::layout::StyleDocument;
+use crate::patch;
+use crate::state::AppState;
+use crate::validate::{self, Violation};
+
+// Lo que ve un handler además de sus args
+pub struct CommandContext<'a> {
+    pub state: &'a AppState,
+    pub message: &'a Value, // frame completo (top-level content, files[], ...)
+    pub source: &'a str,    // msg_id o "broker", para el historial de layouts
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandOutcome {
+    Applied { relayout: bool }, // hecho; relayout = hay que re-emitir layout_update
+    Rejected(String),           // no se aplicó nada, la pantalla sigue igual
+    LayoutRejected(String),     // layout nuevo inválido → se vuelve a last_good
+    NotFound,                   // el comando no traía su contenido (se prueban los demás frames)
+}
+
+pub trait CommandHandler: Send + Sync {
+    type Args: DeserializeOwned;
+    fn handle(&self, args: Self::Args, ctx: &CommandContext) -> CommandOutcome;
+}
+
+// Versión sin tipo de args para guardar handlers distintos en el mismo mapa
+trait ErasedHandler: Send + Sync {
+    fn call(&self, args: Value, ctx: &CommandContext) -> CommandOutcome;
+}
+
+impl<H: CommandHandler> ErasedHandler for H {
+    fn call(&self, args: Value, ctx: &CommandContext) -> CommandOutcome {
+        match serde_json::from_value::<H::Args>(args) {
+            Ok(args) => self.handle(args, ctx),
+            Err(e) => CommandOutcome::Rejected(format!("args inválidos: {e}")),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct CommandRegistry {
+    handlers: Arc<Mutex<HashMap<String, Arc<dyn ErasedHandler>>>>,
+}
+
+impl CommandRegistry {
+    pub fn with_builtins() -> Self {
+        let registry = Self::default();
+        registry.register("ui.apply", ApplyLayout);
+        registry.register("ui.update", ApplyLayout);
+        registry.register("ui.style.apply", ApplyStyle);
+        registry.register("ui.style.update", ApplyStyle);
+        registry.register("ui.screen.show", ShowScreen);
+        registry.register("ui.data.set", SetData);
+        registry.register("ui.rollback", Rollback);
+        registry.register("config.update", UpdateConfig);
+        registry
+    }
+
+    pub fn register(&self, name: &str, handler: impl CommandHandler + 'static) {
+        self.handlers.lock().unwrap().insert(name.to_string(), Arc::new(handler));
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.handlers.lock().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    // None = comando desconocido. El lock no se mantiene mientras corre el handler.
+    pub fn dispatch(&self, name: &str, args: Value, ctx: &CommandContext) -> Option<CommandOutcome> {
+        let handler = self.handlers.lock().unwrap().get(name).cloned()?;
+        Some(handler.call(args, ctx))
+    }
+}
+
+// --------------------- helpers parsing ---------------------
+pub fn parse_json_str(s: &str) -> Option<Value> {
+    serde_json::from_str::<Value>(s).ok()
+}
+pub fn parse_base64_json(s: &str) -> Option<Value> {
+    let bytes = STANDARD.decode(s).ok()?;
+    let txt = String::from_utf8(bytes).ok()?;
+    parse_json_str(&txt)
+}
+
+// Lo que trae un frame: un layout completo o un documento style (+ pantalla pedida)
+pub enum Extracted {
+    Layout(Value),
+    Style { style: Value, screen_id: Option<String> },
+}
+
+pub fn layout_or_style(content: &Value) -> Option<Extracted> {
+    if content.get("root").is_some() {
+        return Some(Extracted::Layout(content.clone()));
+    }
+    if content.get("screens").is_some() {
+        return Some(Extracted::Style { style: content.clone(), screen_id: None });
+    }
+    None
+}
+
+// Layout o style completo → current/lastGood (o LayoutRejected si no valida)
+pub fn apply_extracted(state: &AppState, found: Extracted, source: &str) -> CommandOutcome {
+    let applied = match found {
+        Extracted::Layout(layout_v) => state.apply_layout_safely(&layout_v, source),
+        Extracted::Style { style, screen_id } => match serde_json::from_value::<StyleDocument>(style) {
+            Ok(doc) => state.apply_style(doc, screen_id.as_deref(), source),
+            Err(e) => Err(vec![Violation::new("$", format!("style inválido: {e}"))]),
+        },
+    };
+    match applied {
+        Ok(()) => CommandOutcome::Applied { relayout: true },
+        Err(violations) => CommandOutcome::LayoutRejected(validate::describe(&violations)),
+    }
+}
+
+fn in_place(result: Result<(), Vec<Violation>>) -> CommandOutcome {
+    match result {
+        Ok(()) => CommandOutcome::Applied { relayout: true },
+        Err(violations) => CommandOutcome::Rejected(validate::describe(&violations)),
+    }
+}
+
+// --------------------- ui.apply / ui.update ---------------------
+// Incremental si trae patch / updates / set (ver patch.rs); si no, reemplazo completo
+// con args.content (root o style), top-level content o un style en args.data_base64.
+struct ApplyLayout;
+
+impl CommandHandler for ApplyLayout {
+    type Args = Value;
+
+    fn handle(&self, args: Value, ctx: &CommandContext) -> CommandOutcome {
+        if patch::is_incremental(&args) {
+            return in_place(ctx.state.update_layout(ctx.source, |doc| patch::apply_update_args(doc, &args)));
+        }
+        let found = args
+            .get("content")
+            .and_then(layout_or_style)
+            .or_else(|| ctx.message.get("content").and_then(layout_or_style))
+            .or_else(|| {
+                let style = args.get("data_base64")?.as_str().and_then(parse_base64_json)?;
+                Some(Extracted::Style { style, screen_id: None })
+            });
+        match found {
+            Some(found) => apply_extracted(ctx.state, found, ctx.source),
+            None => CommandOutcome::NotFound,
+        }
+    }
+}
+
+// --------------------- ui.style.apply / ui.style.update ---------------------
+// args.style / args.style_json / args.data_base64 / files[]; args.screen_id elige la pantalla (si no, la primera)
+#[derive(Deserialize)]
+struct StyleArgs {
+    #[serde(default)]
+    screen_id: Option<String>,
+    #[serde(default)]
+    style: Option<Value>,
+    #[serde(default)]
+    style_json: Option<String>,
+    #[serde(default)]
+    data_base64: Option<String>,
+}
+
+struct ApplyStyle;
+
+impl CommandHandler for ApplyStyle {
+    type Args = StyleArgs;
+
+    fn handle(&self, args: StyleArgs, ctx: &CommandContext) -> CommandOutcome {
+        let style = args
+            // a) objeto style directo
+            .style
+            // b) style en string JSON
+            .or_else(|| args.style_json.as_deref().and_then(parse_json_str))
+            // c) style en base64 (TU CASO)
+            .or_else(|| args.data_base64.as_deref().and_then(parse_base64_json))
+            // d) style referenciado en files (content/text/base64)
+            .or_else(|| find_style_in_files(ctx.message));
+        match style {
+            Some(style) => apply_extracted(ctx.state, Extracted::Style { style, screen_id: args.screen_id }, ctx.source),
+            None => CommandOutcome::NotFound,
+        }
+    }
+}
+
+// Busca un objeto style en files[] (content/text/base64)
+fn find_style_in_files(root: &Value) -> Option<Value> {
+    let files = root.get("files")?.as_array()?;
+    let mut candidates: Vec<&Value> = files.iter().collect();
+    candidates.sort_by_key(|f| {
+        let name = f.get("name").and_then(|v| v.as_str()).unwrap_or("");
+        let score = if name.to_ascii_lowercase().contains("style") { 0 } else { 1 };
+        (score, name.len())
+    });
+
+    for f in candidates {
+        if let Some(c) = f.get("content") {
+            if c.get("screens").is_some() { return Some(c.clone()); }
+            if let Some(txt) = c.as_str() {
+                if let Some(v) = parse_json_str(txt) {
+                    if v.get("screens").is_some() { return Some(v); }
+                }
+            }
+        }
+        if let Some(txt) = f.get("text").and_then(|v| v.as_str()) {
+            if let Some(v) = parse_json_str(txt) {
+                if v.get("screens").is_some() { return Some(v); }
+            }
+        }
+        for key in &["content_b64", "base64", "bytes_b64"] {
+            if let Some(b64) = f.get(*key).and_then(|v| v.as_str()) {
+                if let Some(v) = parse_base64_json(b64) {
+                    if v.get("screens").is_some() { return Some(v); }
+                }
+            }
+        }
+    }
+    None
+}
+
+// --------------------- ui.screen.show → pantalla del style cacheado ---------------------
+#[derive(Deserialize)]
+struct ScreenArgs {
+    id: String,
+}
+
+struct ShowScreen;
+
+impl CommandHandler for ShowScreen {
+    type Args = ScreenArgs;
+
+    fn handle(&self, args: ScreenArgs, ctx: &CommandContext) -> CommandOutcome {
+        in_place(ctx.state.show_screen(&args.id, ctx.source))
+    }
+}
+
+// --------------------- ui.data.set → data-context ---------------------
+// args: { "path": "msr.track1", "value": ... }  o  { "values": { "msr.track1": ..., ... } }
+#[derive(Deserialize)]
+struct DataArgs {
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default)]
+    value: Value,
+    #[serde(default)]
+    values: Map<String, Value>,
+}
+
+struct SetData;
+
+impl CommandHandler for SetData {
+    type Args = DataArgs;
+
+    fn handle(&self, args: DataArgs, ctx: &CommandContext) -> CommandOutcome {
+        let mut relayout = false;
+        if let Some(path) = &args.path {
+            relayout |= ctx.state.set_data(path, args.value);
+        }
+        for (path, value) in args.values {
+            relayout |= ctx.state.set_data(&path, value);
+        }
+        CommandOutcome::Applied { relayout }
+    }
+}
+
+// --------------------- ui.rollback → revisión anterior del historial ---------------------
+// args: { "steps": 1 } (por defecto) o { "version": 12 }
+#[derive(Deserialize)]
+struct RollbackArgs {
+    #[serde(default)]
+    steps: Option<u64>,
+    #[serde(default)]
+    version: Option<u64>,
+}
+
+struct Rollback;
+
+impl CommandHandler for Rollback {
+    type Args = RollbackArgs;
+
+    fn handle(&self, args: RollbackArgs, ctx: &CommandContext) -> CommandOutcome {
+        match ctx.state.rollback(args.steps, args.version) {
+            Ok(version) => {
+                eprintln!("[ZMQ] ui.rollback → versión {version}");
+                CommandOutcome::Applied { relayout: true }
+            }
+            Err(e) => CommandOutcome::Rejected(e),
+        }
+    }
+}
+
+// --------------------- config.update → config en caliente ---------------------
+// args: objeto parcial de la config, p.ej. { "broker": { "sub_endpoint": "tcp://10.0.0.5:5557" } }
+// Sólo endpoint y tiempos: firmas, llaves y URLs de salida no se tocan desde el broker.
+struct UpdateConfig;
+
+impl CommandHandler for UpdateConfig {
+    type Args = Value;
+
+    fn handle(&self, changes: Value, ctx: &CommandContext) -> CommandOutcome {
+        match ctx.state.update_config_from_broker(changes) {
+            Ok(cfg) => {
+                eprintln!("[ZMQ] config.update aplicado (broker {})", cfg.broker.sub_endpoint);
+                CommandOutcome::Applied { relayout: false }
+            }
+            Err(errors) => CommandOutcome::Rejected(errors.join("; ")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{column, layout, text};
+    use serde_json::json;
+
+    fn state() -> AppState {
+        AppState::new(layout(column().child(text("t", "inicio"))))
+    }
+
+    fn dispatch(st: &AppState, name: &str, args: Value) -> Option<CommandOutcome> {
+        let ctx = CommandContext { state: st, message: &Value::Null, source: "test" };
+        st.commands.dispatch(name, args, &ctx)
+    }
+
+    #[derive(Deserialize)]
+    struct BeepArgs {
+        times: u32,
+    }
+
+    // Comando de prueba: deja `beeps` en el data-context
+    struct Beep;
+
+    impl CommandHandler for Beep {
+        type Args = BeepArgs;
+
+        fn handle(&self, args: BeepArgs, ctx: &CommandContext) -> CommandOutcome {
+            if args.times == 0 {
+                return CommandOutcome::Rejected("times debe ser > 0".to_string());
+            }
+            let relayout = ctx.state.set_data("beeps", json!(args.times));
+            CommandOutcome::Applied { relayout }
+        }
+    }
+
+    #[test]
+    fn custom_handler_is_dispatched() {
+        let st = state();
+        assert!(dispatch(&st, "device.beep", json!({ "times": 2 })).is_none());
+        st.commands.register("device.beep", Beep);
+        assert!(st.commands.names().contains(&"device.beep".to_string()));
+        assert_eq!(dispatch(&st, "device.beep", json!({ "times": 2 })), Some(CommandOutcome::Applied { relayout: false }));
+        assert_eq!(st.get_data()["beeps"], 2);
+        assert_eq!(
+            dispatch(&st, "device.beep", json!({ "times": 0 })),
+            Some(CommandOutcome::Rejected("times debe ser > 0".to_string()))
+        );
+    }
+
+    #[test]
+    fn badly_typed_args_are_rejected() {
+        let st = state();
+        st.commands.register("device.beep", Beep);
+        for args in [json!({ "times": "dos" }), json!({}), json!("dos")] {
+            let Some(CommandOutcome::Rejected(msg)) = dispatch(&st, "device.beep", args.clone()) else {
+                panic!("se esperaba Rejected para {args}");
+            };
+            assert!(msg.starts_with("args inválidos: "), "{msg}");
+        }
+        // builtin: rollback con steps negativo tampoco llega al handler
+        let Some(CommandOutcome::Rejected(msg)) = dispatch(&st, "ui.rollback", json!({ "steps": -1 })) else {
+            panic!("se esperaba Rejected");
+        };
+        assert!(msg.starts_with("args inválidos: "), "{msg}");
+        assert!(st.get_data().get("beeps").is_none());
+    }
+
+    #[test]
+    fn builtins_are_registered() {
+        let names = CommandRegistry::with_builtins().names();
+        for name in ["ui.apply", "ui.update", "ui.style.apply", "ui.screen.show", "ui.data.set", "ui.rollback", "config.update"] {
+            assert!(names.contains(&name.to_string()), "{name}");
+        }
+        assert!(dispatch(&state(), "ui.nuevo", json!({})).is_none());
+    }
+}
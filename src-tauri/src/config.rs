@@ -0,0 +1,617 @@
+// Configuración tipada de la app (reemplaza las IPs fijas y los env sueltos).
+//
+// Orden de precedencia (lo de abajo pisa lo de arriba):
+//   1. valores por defecto (AppConfig::default)
+//   2. config.toml / config.json en el app config dir (o --config <archivo>)
+//   3. el perfil elegido: [profiles.<nombre>] del mismo archivo
+//      (--profile <nombre>, si no TAURI_PROFILE, si no `profile` del archivo)
+//   4. variables de entorno (TAURI_ZMQ_SUB, TAURI_ACK_URL, ... ver ENV_OVERRIDES)
+//   5. CLI: --set broker.sub_endpoint=tcp://10.0.0.5:5557 (repetible)
+//
+//   # config.toml
+//   profile = "prod"
+//   locale = "es-CO"
+//   [broker]
+//   sub_endpoint = "tcp://127.0.0.1:5557"
+//   [profiles.prod.broker]
+//   sub_endpoint = "tcp://34.70.157.148:5557"
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::template::set_path;
+
+const FILE_NAMES: [&str; 2] = ["config.toml", "config.json"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BrokerConfig {
+    pub sub_endpoint: String,
+    // JSON con las llaves de firma (ver signature.rs). Con llaves la firma ya es obligatoria
+    // (salvo allow_unsigned en ese archivo); require_signed la exige aunque no haya ninguna.
+    pub keys_file: Option<String>,
+    pub require_signed: bool,
+    // JSON con las llaves CURVE (ver curve.rs)
+    pub curve_keys_file: Option<String>,
+    pub stall_after_secs: u64,
+    pub reset_after_secs: u64,
+}
+
+impl Default for BrokerConfig {
+    fn default() -> Self {
+        Self {
+            sub_endpoint: "tcp://34.70.157.148:5557".to_string(),
+            keys_file: None,
+            require_signed: false,
+            curve_keys_file: None,
+            stall_after_secs: 90,
+            reset_after_secs: 300,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HeartbeatConfig {
+    pub url: String,
+    pub interval_secs: u64,
+    pub timeout_secs: u64,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self { url: "http://34.70.157.148:8080/heartbeat".to_string(), interval_secs: 15, timeout_secs: 5 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AckConfig {
+    pub url: String,
+    pub timeout_secs: u64,
+}
+
+impl Default for AckConfig {
+    fn default() -> Self {
+        Self { url: "http://34.70.157.148:8080/ack".to_string(), timeout_secs: 5 }
+    }
+}
+
+// Eventos de UI y requests hacia el backend (ver upstream.rs). Sin endpoint no se envía nada;
+// activarlo requiere reiniciar, cambiarlo o desactivarlo se aplica en caliente.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpstreamConfig {
+    pub endpoint: Option<String>,
+    // además de los no manejados localmente, estos se envían siempre
+    pub forward_events: Vec<String>,
+    // espera por defecto de un request (cada llamada puede pedir otra)
+    pub request_timeout_ms: u64,
+}
+
+impl Default for UpstreamConfig {
+    fn default() -> Self {
+        Self { endpoint: None, forward_events: Vec::new(), request_timeout_ms: 5_000 }
+    }
+}
+
+// Lector de banda magnética (ver msr.rs); cambiarlo requiere reiniciar
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MsrKind {
+    #[default]
+    Wedge, // el lector "teclea" la banda en la ventana
+    Serial,
+    Simulated,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MsrConfig {
+    pub reader: MsrKind,
+    // serial: "/dev/ttyUSB0", "COM3"
+    pub serial_port: Option<String>,
+    pub serial_baud: u32,
+    // simulated: swipes de este archivo (uno por línea) en vez de esperar msr_simulate_swipe
+    pub sim_file: Option<String>,
+    pub sim_delay_ms: u64,
+    pub read_timeout_secs: u64,
+}
+
+impl Default for MsrConfig {
+    fn default() -> Self {
+        Self {
+            reader: MsrKind::Wedge,
+            serial_port: None,
+            serial_baud: 9600,
+            sim_file: None,
+            sim_delay_ms: 1_500,
+            read_timeout_secs: 30,
+        }
+    }
+}
+
+// Timeouts de cada estado del cobro (ver payment.rs), en segundos
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaymentConfig {
+    pub amount_entry_secs: u64,
+    pub authorizing_secs: u64,
+    // aprobado / rechazado sin que nadie toque nada → vuelve a inicio
+    pub result_secs: u64,
+    pub receipt_secs: u64,
+}
+
+impl Default for PaymentConfig {
+    fn default() -> Self {
+        Self { amount_entry_secs: 120, authorizing_secs: 45, result_secs: 60, receipt_secs: 10 }
+    }
+}
+
+// Quién autoriza los cobros (ver processor.rs); cambiarlo requiere reiniciar
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProcessorKind {
+    #[default]
+    Mock,
+    Http,   // stand-in local en processor.http_url
+    Broker, // request/reply por upstream
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProcessorConfig {
+    pub kind: ProcessorKind,
+    // http: base de /authorize, /capture, /void, /refund
+    pub http_url: Option<String>,
+    pub http_timeout_ms: u64,
+    // mock: ver MockProcessor::decide
+    pub mock_latency_ms: u64,
+    pub mock_decline_over_cents: u64,
+    pub mock_decline_last4: Vec<String>,
+}
+
+impl Default for ProcessorConfig {
+    fn default() -> Self {
+        Self {
+            kind: ProcessorKind::Mock,
+            http_url: None,
+            http_timeout_ms: 10_000,
+            mock_latency_ms: 1_200,
+            mock_decline_over_cents: 500_000,
+            mock_decline_last4: vec!["0002".to_string()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Features {
+    pub heartbeat: bool,
+    pub acks: bool,
+    pub persistence: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Self { heartbeat: true, acks: true, persistence: true }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    pub profile: String,
+    // None → hostname del equipo
+    pub device_id: Option<String>,
+    pub locale: String,
+    pub broker: BrokerConfig,
+    pub heartbeat: HeartbeatConfig,
+    pub ack: AckConfig,
+    pub upstream: UpstreamConfig,
+    pub msr: MsrConfig,
+    pub payment: PaymentConfig,
+    pub processor: ProcessorConfig,
+    pub features: Features,
+    // de dónde salió (sólo informativo; lo llena load)
+    pub source_file: Option<String>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            profile: "default".to_string(),
+            device_id: None,
+            locale: "es-CO".to_string(),
+            broker: BrokerConfig::default(),
+            heartbeat: HeartbeatConfig::default(),
+            ack: AckConfig::default(),
+            upstream: UpstreamConfig::default(),
+            msr: MsrConfig::default(),
+            payment: PaymentConfig::default(),
+            processor: ProcessorConfig::default(),
+            features: Features::default(),
+            source_file: None,
+        }
+    }
+}
+
+impl AppConfig {
+    pub fn device_id(&self) -> String {
+        self.device_id
+            .clone()
+            .or_else(|| {
+                ["HOSTNAME", "COMPUTERNAME"]
+                    .iter()
+                    .find_map(|k| std::env::var(k).ok().filter(|v| !v.trim().is_empty()))
+            })
+            .unwrap_or_else(|| "unknown-device".to_string())
+    }
+
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        let b = &self.broker;
+        if !["tcp://", "ipc://", "inproc://"].iter().any(|p| b.sub_endpoint.starts_with(p)) {
+            errors.push(format!("broker.sub_endpoint: '{}' no es un endpoint zmq (tcp://, ipc://, inproc://)", b.sub_endpoint));
+        }
+        if b.stall_after_secs == 0 || b.reset_after_secs == 0 {
+            errors.push("broker.stall_after_secs / reset_after_secs deben ser > 0".to_string());
+        }
+        if b.require_signed && b.keys_file.is_none() {
+            errors.push("broker.require_signed sin broker.keys_file: se rechazaría todo".to_string());
+        }
+        for (name, path) in [("broker.keys_file", &b.keys_file), ("broker.curve_keys_file", &b.curve_keys_file)] {
+            if let Some(p) = path.as_deref().filter(|p| !Path::new(p).is_file()) {
+                errors.push(format!("{name}: no existe '{p}'"));
+            }
+        }
+        if let Some(ep) = self.upstream.endpoint.as_deref() {
+            if !["tcp://", "ipc://", "inproc://"].iter().any(|p| ep.starts_with(p)) {
+                errors.push(format!("upstream.endpoint: '{ep}' no es un endpoint zmq (tcp://, ipc://, inproc://)"));
+            }
+        }
+        if self.upstream.request_timeout_ms == 0 {
+            errors.push("upstream.request_timeout_ms debe ser > 0".to_string());
+        }
+        let m = &self.msr;
+        if m.reader == MsrKind::Serial && m.serial_port.as_deref().is_none_or(|p| p.trim().is_empty()) {
+            errors.push("msr.reader = \"serial\" requiere msr.serial_port".to_string());
+        }
+        if m.serial_baud == 0 || m.read_timeout_secs == 0 {
+            errors.push("msr.serial_baud / msr.read_timeout_secs deben ser > 0".to_string());
+        }
+        if let Some(p) = m.sim_file.as_deref().filter(|p| !Path::new(p).is_file()) {
+            errors.push(format!("msr.sim_file: no existe '{p}'"));
+        }
+        let p = &self.payment;
+        if [p.amount_entry_secs, p.authorizing_secs, p.result_secs, p.receipt_secs].contains(&0) {
+            errors.push("payment.*_secs deben ser > 0".to_string());
+        }
+        let pr = &self.processor;
+        match pr.kind {
+            ProcessorKind::Http if !pr.http_url.as_deref().is_some_and(|u| u.starts_with("http://") || u.starts_with("https://")) => {
+                errors.push("processor.kind = \"http\" requiere processor.http_url http(s)".to_string());
+            }
+            ProcessorKind::Broker if self.upstream.endpoint.is_none() => {
+                errors.push("processor.kind = \"broker\" requiere upstream.endpoint".to_string());
+            }
+            _ => {}
+        }
+        if pr.http_timeout_ms == 0 {
+            errors.push("processor.http_timeout_ms debe ser > 0".to_string());
+        }
+        for (name, url) in [("heartbeat.url", &self.heartbeat.url), ("ack.url", &self.ack.url)] {
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                errors.push(format!("{name}: '{url}' no es una URL http(s)"));
+            }
+        }
+        if self.heartbeat.interval_secs == 0 {
+            errors.push("heartbeat.interval_secs debe ser > 0".to_string());
+        }
+        if self.heartbeat.timeout_secs == 0 || self.ack.timeout_secs == 0 {
+            errors.push("heartbeat.timeout_secs / ack.timeout_secs deben ser > 0".to_string());
+        }
+        if self.device_id.as_deref().is_some_and(|d| d.trim().is_empty()) {
+            errors.push("device_id: vacío (omitirlo para usar el hostname)".to_string());
+        }
+        if !is_locale(&self.locale) {
+            errors.push(format!("locale: '{}' no tiene forma ll o ll-RR (ej. es-CO)", self.locale));
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
+fn is_locale(s: &str) -> bool {
+    let mut parts = s.split('-');
+    let lang = parts.next().unwrap_or_default();
+    let region = parts.next();
+    parts.next().is_none()
+        && lang.len() == 2
+        && lang.chars().all(|c| c.is_ascii_lowercase())
+        && region.is_none_or(|r| r.len() == 2 && r.chars().all(|c| c.is_ascii_uppercase()))
+}
+
+// Cómo se interpreta el valor de cada variable de entorno
+#[derive(Debug, Clone, Copy)]
+enum EnvKind {
+    Text,
+    Number,
+    // 1/0, yes/no, si/no, on/off, true/false
+    Flag,
+}
+
+// Env de antes → ruta en la config
+const ENV_OVERRIDES: [(&str, &str, EnvKind); 14] = [
+    ("TAURI_ZMQ_SUB", "broker.sub_endpoint", EnvKind::Text),
+    ("TAURI_BROKER_KEYS", "broker.keys_file", EnvKind::Text),
+    ("TAURI_REQUIRE_SIGNED", "broker.require_signed", EnvKind::Flag),
+    ("TAURI_ZMQ_CURVE_KEYS", "broker.curve_keys_file", EnvKind::Text),
+    ("TAURI_HB_URL", "heartbeat.url", EnvKind::Text),
+    ("TAURI_HB_INTERVAL_SECS", "heartbeat.interval_secs", EnvKind::Number),
+    ("TAURI_ACK_URL", "ack.url", EnvKind::Text),
+    ("TAURI_DEVICE_ID", "device_id", EnvKind::Text),
+    ("TAURI_LOCALE", "locale", EnvKind::Text),
+    ("TAURI_ZMQ_UPSTREAM", "upstream.endpoint", EnvKind::Text),
+    ("TAURI_MSR_READER", "msr.reader", EnvKind::Text),
+    ("TAURI_MSR_PORT", "msr.serial_port", EnvKind::Text),
+    ("TAURI_PAY_PROCESSOR", "processor.kind", EnvKind::Text),
+    ("TAURI_PAY_URL", "processor.http_url", EnvKind::Text),
+];
+
+fn env_value(kind: EnvKind, raw: String) -> Value {
+    match kind {
+        EnvKind::Text => Value::String(raw),
+        EnvKind::Number => parse_scalar(&raw),
+        // lo que no se reconoce queda como string y serde dice qué variable está mal
+        EnvKind::Flag => match raw.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "si" | "sí" | "on" => Value::Bool(true),
+            "0" | "false" | "no" | "off" | "" => Value::Bool(false),
+            _ => Value::String(raw),
+        },
+    }
+}
+
+fn apply_env(doc: &mut Value, var: impl Fn(&str) -> Option<String>) {
+    for (name, path, kind) in ENV_OVERRIDES {
+        if let Some(raw) = var(name) {
+            set_path(doc, path, env_value(kind, raw));
+        }
+    }
+}
+
+// Campos bool de la config: en --set se aceptan las mismas formas que en el env (yes/si/on/1...)
+const BOOL_PATHS: [&str; 4] = ["broker.require_signed", "features.heartbeat", "features.acks", "features.persistence"];
+
+fn cli_value(path: &str, raw: &str) -> Value {
+    if BOOL_PATHS.contains(&path) {
+        env_value(EnvKind::Flag, raw.to_string())
+    } else {
+        parse_scalar(raw)
+    }
+}
+
+// "15" → 15, "true" → true, lo demás queda como string
+fn parse_scalar(raw: &str) -> Value {
+    match serde_json::from_str::<Value>(raw) {
+        Ok(v) if !v.is_object() && !v.is_array() => v,
+        _ => Value::String(raw.to_string()),
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CliOverrides {
+    pub profile: Option<String>,
+    pub file: Option<PathBuf>,
+    pub sets: Vec<(String, Value)>,
+}
+
+// --profile <nombre>, --config <archivo>, --set clave=valor; el resto se ignora (es de tauri)
+pub fn parse_cli(args: impl IntoIterator<Item = String>) -> Result<CliOverrides, String> {
+    let mut out = CliOverrides::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value_of = |flag: &str| args.next().ok_or_else(|| format!("{flag} requiere un valor"));
+        match arg.as_str() {
+            "--profile" => out.profile = Some(value_of("--profile")?),
+            "--config" => out.file = Some(PathBuf::from(value_of("--config")?)),
+            "--set" => {
+                let kv = value_of("--set")?;
+                let (key, raw) = kv.split_once('=').ok_or_else(|| format!("--set '{kv}': se espera clave=valor"))?;
+                let key = key.trim();
+                out.sets.push((key.to_string(), cli_value(key, raw)));
+            }
+            _ => {}
+        }
+    }
+    Ok(out)
+}
+
+fn read_file(path: &Path) -> Result<Value, String> {
+    let txt = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    if path.extension().is_some_and(|e| e == "json") {
+        serde_json::from_str(&txt).map_err(|e| format!("{}: {e}", path.display()))
+    } else {
+        toml::from_str(&txt).map_err(|e| format!("{}: {e}", path.display()))
+    }
+}
+
+// Mezcla `over` sobre `base` (objetos recursivo; el resto se reemplaza)
+fn merge(base: &mut Value, over: Value) {
+    match (base, over) {
+        (Value::Object(b), Value::Object(o)) => {
+            for (k, v) in o {
+                merge(b.entry(k).or_insert(Value::Null), v);
+            }
+        }
+        (slot, v) => *slot = v,
+    }
+}
+
+// Cambio en caliente (config.update / broker_set_endpoint): mismo merge y misma validación
+pub fn apply_update(current: &AppConfig, changes: Value) -> Result<AppConfig, Vec<String>> {
+    if !changes.is_object() {
+        return Err(vec!["se espera un objeto con los campos a cambiar".to_string()]);
+    }
+    let mut doc = serde_json::to_value(current).map_err(|e| vec![e.to_string()])?;
+    merge(&mut doc, changes);
+    let config: AppConfig = serde_json::from_value(doc).map_err(|e| vec![format!("config inválida: {e}")])?;
+    config.validate()?;
+    Ok(config)
+}
+
+// Lo único que puede cambiar un config.update que llega por el broker: endpoint del SUB y
+// tiempos. Firmas, llaves y las URLs a donde salen ACKs / heartbeat / cobros quedan fuera,
+// para que un mensaje del broker no pueda apagar la verificación ni desviar datos.
+const BROKER_UPDATABLE: [&str; 13] = [
+    "broker.sub_endpoint",
+    "broker.stall_after_secs",
+    "broker.reset_after_secs",
+    "heartbeat.interval_secs",
+    "heartbeat.timeout_secs",
+    "ack.timeout_secs",
+    "upstream.request_timeout_ms",
+    "msr.read_timeout_secs",
+    "payment.amount_entry_secs",
+    "payment.authorizing_secs",
+    "payment.result_secs",
+    "payment.receipt_secs",
+    "locale",
+];
+
+fn leaf_paths(v: &Value, prefix: &str, out: &mut Vec<String>) {
+    match v.as_object() {
+        Some(obj) if !obj.is_empty() => {
+            for (k, child) in obj {
+                let path = if prefix.is_empty() { k.clone() } else { format!("{prefix}.{k}") };
+                leaf_paths(child, &path, out);
+            }
+        }
+        _ => out.push(prefix.to_string()),
+    }
+}
+
+// config.update del broker: rechaza (todo el cambio) si toca algo fuera de BROKER_UPDATABLE
+pub fn check_broker_update(changes: &Value) -> Result<(), Vec<String>> {
+    let mut paths = Vec::new();
+    leaf_paths(changes, "", &mut paths);
+    let denied: Vec<String> = paths
+        .into_iter()
+        .filter(|p| !BROKER_UPDATABLE.contains(&p.as_str()))
+        .map(|p| format!("'{p}' no se puede cambiar desde el broker"))
+        .collect();
+    if denied.is_empty() { Ok(()) } else { Err(denied) }
+}
+
+pub fn load(config_dir: &Path, cli: &CliOverrides) -> Result<AppConfig, Vec<String>> {
+    let mut doc = serde_json::to_value(AppConfig::default()).map_err(|e| vec![e.to_string()])?;
+
+    // 2) archivo (el explícito tiene que existir; el del config dir es opcional)
+    let file = match &cli.file {
+        Some(p) => Some(p.clone()),
+        None => FILE_NAMES.iter().map(|n| config_dir.join(n)).find(|p| p.is_file()),
+    };
+    let mut profiles = Value::Null;
+    if let Some(path) = &file {
+        let mut from_file = read_file(path).map_err(|e| vec![e])?;
+        if let Some(obj) = from_file.as_object_mut() {
+            profiles = obj.remove("profiles").unwrap_or(Value::Null);
+        }
+        merge(&mut doc, from_file);
+    }
+
+    // 3) perfil
+    let profile = cli
+        .profile
+        .clone()
+        .or_else(|| std::env::var("TAURI_PROFILE").ok())
+        .or_else(|| doc.get("profile").and_then(|p| p.as_str()).map(str::to_string))
+        .unwrap_or_else(|| "default".to_string());
+    match profiles.get(&profile) {
+        Some(over) => merge(&mut doc, over.clone()),
+        None if profile != "default" => return Err(vec![format!("perfil '{profile}' no existe en la config")]),
+        None => {}
+    }
+    set_path(&mut doc, "profile", Value::String(profile));
+
+    // 4) env  5) CLI
+    apply_env(&mut doc, |name| std::env::var(name).ok());
+    for (path, value) in &cli.sets {
+        set_path(&mut doc, path, value.clone());
+    }
+
+    let mut config: AppConfig = serde_json::from_value(doc).map_err(|e| vec![format!("config inválida: {e}")])?;
+    config.source_file = file.map(|p| p.display().to_string());
+    config.validate()?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn with_env(vars: &[(&str, &str)]) -> Result<AppConfig, String> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let mut doc = serde_json::to_value(AppConfig::default()).unwrap();
+        apply_env(&mut doc, |name| vars.get(name).cloned());
+        serde_json::from_value(doc).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn require_signed_accepts_usual_flag_spellings() {
+        for (raw, expected) in [("1", true), ("yes", true), ("TRUE", true), ("0", false), ("no", false), ("false", false)] {
+            let config = with_env(&[("TAURI_REQUIRE_SIGNED", raw), ("TAURI_BROKER_KEYS", "/etc/keys.json")]).unwrap();
+            assert_eq!(config.broker.require_signed, expected, "{raw}");
+        }
+        assert!(with_env(&[("TAURI_REQUIRE_SIGNED", "quizás")]).unwrap_err().contains("expected a boolean"));
+    }
+
+    #[test]
+    fn broker_updates_cannot_touch_security_settings() {
+        let ok = serde_json::json!({ "broker": { "sub_endpoint": "tcp://10.0.0.5:5557" }, "heartbeat": { "interval_secs": 30 } });
+        assert!(check_broker_update(&ok).is_ok());
+        for denied in [
+            serde_json::json!({ "broker": { "require_signed": false } }),
+            serde_json::json!({ "broker": { "keys_file": null } }),
+            serde_json::json!({ "broker": { "curve_keys_file": "/tmp/k.json" } }),
+            serde_json::json!({ "ack": { "url": "http://evil/ack" } }),
+            serde_json::json!({ "heartbeat": { "url": "http://evil/hb" } }),
+            serde_json::json!({ "processor": { "http_url": "http://evil/pay" } }),
+            serde_json::json!({ "broker": null }),
+        ] {
+            assert!(check_broker_update(&denied).is_err(), "{denied}");
+        }
+        // una parte prohibida tumba todo el cambio
+        let mixed = serde_json::json!({ "broker": { "sub_endpoint": "tcp://x:1", "require_signed": false } });
+        assert_eq!(check_broker_update(&mixed).unwrap_err(), ["'broker.require_signed' no se puede cambiar desde el broker"]);
+    }
+
+    #[test]
+    fn cli_set_coerces_bool_fields() {
+        let args = ["--set", "broker.require_signed=yes", "--set", "features.acks=off", "--set", "features.heartbeat=SI"];
+        let cli = parse_cli(["--verbose", "--set", "heartbeat.interval_secs=30"].into_iter().chain(args).map(String::from)).unwrap();
+        let mut doc = serde_json::to_value(AppConfig::default()).unwrap();
+        for (path, value) in &cli.sets {
+            set_path(&mut doc, path, value.clone());
+        }
+        let config: AppConfig = serde_json::from_value(doc).unwrap();
+        assert!(config.broker.require_signed);
+        assert!(!config.features.acks);
+        assert!(config.features.heartbeat);
+        assert_eq!(config.heartbeat.interval_secs, 30);
+
+        // lo que no es un bool reconocible sigue llegando a serde como string
+        let bad = parse_cli(["--set", "features.persistence=quizás"].map(String::from)).unwrap();
+        assert_eq!(bad.sets, [("features.persistence".to_string(), Value::String("quizás".to_string()))]);
+        assert!(parse_cli(["--set", "sin_igual"].map(String::from)).is_err());
+    }
+
+    #[test]
+    fn env_numbers_and_text_keep_their_type() {
+        let config = with_env(&[("TAURI_HB_INTERVAL_SECS", "30"), ("TAURI_DEVICE_ID", "0042")]).unwrap();
+        assert_eq!(config.heartbeat.interval_secs, 30);
+        assert_eq!(config.device_id.as_deref(), Some("0042"));
+    }
+}
@@ -0,0 +1,170 @@
+// CURVE (cifrado + autenticación del broker) para el socket SUB.
+// Se activa con broker.curve_keys_file = ruta a un JSON con llaves Z85 (40 caracteres):
+//
+//   { "client_public": "...", "client_secret": "...", "server_public": "..." }
+//
+// Si está configurado y algo falla (archivo, llaves, libzmq sin CURVE) NO se cae a
+// texto plano: la sesión no arranca y el error queda en broker_status.
+
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct KeysFile {
+    client_public: Option<String>,
+    client_secret: Option<String>,
+    server_public: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct CurveKeys {
+    client_public: Vec<u8>,
+    client_secret: Vec<u8>,
+    server_public: Vec<u8>,
+}
+
+// Z85 de una llave de 32 bytes
+fn decode_key(name: &str, value: Option<String>) -> Result<Vec<u8>, String> {
+    let z85 = value.filter(|v| !v.trim().is_empty()).ok_or_else(|| format!("falta '{name}'"))?;
+    let bytes = zmq::z85_decode(z85.trim()).map_err(|e| format!("'{name}' no es Z85 válido: {e}"))?;
+    if bytes.len() != 32 {
+        return Err(format!("'{name}' debe ser una llave de 32 bytes (40 caracteres Z85)"));
+    }
+    Ok(bytes)
+}
+
+impl CurveKeys {
+    // Ok(None) = CURVE no configurado (texto plano, como antes)
+    pub fn load(keys_file: Option<&str>) -> Result<Option<Self>, String> {
+        let Some(path) = keys_file else {
+            return Ok(None);
+        };
+        Self::from_file(path).map(Some).map_err(|e| format!("CURVE ({path}): {e}"))
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let txt = std::fs::read_to_string(path).map_err(|e| format!("no se pudo leer el archivo de llaves: {e}"))?;
+        let file: KeysFile = serde_json::from_str(&txt).map_err(|e| format!("archivo de llaves inválido: {e}"))?;
+        Ok(Self {
+            client_public: decode_key("client_public", file.client_public)?,
+            client_secret: decode_key("client_secret", file.client_secret)?,
+            server_public: decode_key("server_public", file.server_public)?,
+        })
+    }
+
+    // Hay que llamarlo antes de connect()
+    pub fn apply(&self, socket: &zmq::Socket) -> Result<(), String> {
+        if zmq::has("curve") != Some(true) {
+            return Err("esta libzmq se compiló sin soporte CURVE (libsodium)".to_string());
+        }
+        socket.set_curve_serverkey(&self.server_public).map_err(|e| format!("CURVE server key: {e}"))?;
+        socket.set_curve_publickey(&self.client_public).map_err(|e| format!("CURVE public key: {e}"))?;
+        socket.set_curve_secretkey(&self.client_secret).map_err(|e| format!("CURVE secret key: {e}"))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // llaves de ejemplo de la documentación de libzmq (zmq_curve)
+    const SERVER_PUBLIC: &str = "rq:rM>}U?@Lns47E1%kR.o@n%FcmmsL/@{H8]yf7";
+    const SERVER_SECRET: &str = "JTKVSB%%)wK0E.X)V>+}o?pNmC{O&4W4b!Ni{Lh6";
+    const CLIENT_PUBLIC: &str = "Yne@$w-vo<fVvi]a<NY6T1ed:M$fCG*[IaLV{hID";
+    const CLIENT_SECRET: &str = "D:)Q[IlAW!ahhC2ac:9*A}h:p?([4%wOTJ%JR%cs";
+
+    fn keys_file(name: &str, server_public: &str) -> String {
+        let path = std::env::temp_dir().join(format!("curve-test-{name}-{}.json", std::process::id()));
+        let body = serde_json::json!({
+            "client_public": CLIENT_PUBLIC,
+            "client_secret": CLIENT_SECRET,
+            "server_public": server_public,
+        });
+        std::fs::write(&path, body.to_string()).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn not_configured_is_plain_text() {
+        assert!(CurveKeys::load(None).unwrap().is_none());
+    }
+
+    #[test]
+    fn loads_valid_keys() {
+        let keys = CurveKeys::load(Some(&keys_file("ok", SERVER_PUBLIC))).unwrap().unwrap();
+        assert_eq!(keys.server_public, zmq::z85_decode(SERVER_PUBLIC).unwrap());
+        assert_eq!(keys.client_secret.len(), 32);
+    }
+
+    #[test]
+    fn missing_server_key_is_an_error() {
+        let path = keys_file("no-server", " ");
+        let err = CurveKeys::load(Some(&path)).err().unwrap();
+        assert!(err.contains("falta 'server_public'"), "{err}");
+        assert!(err.contains(&path), "{err}");
+    }
+
+    #[test]
+    fn key_length_and_encoding_are_checked() {
+        // 20 caracteres Z85 = 16 bytes
+        assert!(decode_key("k", Some(SERVER_PUBLIC[..20].to_string())).unwrap_err().contains("32 bytes"));
+        assert!(decode_key("k", Some("abc".to_string())).unwrap_err().contains("no es Z85"));
+        assert!(decode_key("k", None).unwrap_err().contains("falta 'k'"));
+        assert!(decode_key("k", Some(format!(" {SERVER_PUBLIC} "))).is_ok());
+        assert!(CurveKeys::load(Some("/no/existe/curve.json")).err().unwrap().contains("no se pudo leer"));
+        let bad = std::env::temp_dir().join(format!("curve-test-bad-json-{}.json", std::process::id()));
+        std::fs::write(&bad, "[1, 2]").unwrap();
+        assert!(CurveKeys::load(bad.to_str()).err().unwrap().contains("archivo de llaves inválido"));
+    }
+
+    // SUB con las llaves del archivo contra un PUB CURVE. None = no llegó nada.
+    fn handshake(endpoint: &str, client_server_key: &str) -> Option<Vec<u8>> {
+        let ctx = zmq::Context::new();
+        let publisher = ctx.socket(zmq::PUB).unwrap();
+        publisher.set_curve_server(true).unwrap();
+        publisher.set_curve_secretkey(&zmq::z85_decode(SERVER_SECRET).unwrap()).unwrap();
+        publisher.bind(endpoint).unwrap();
+
+        let keys = CurveKeys::from_file(&keys_file(&endpoint.replace([':', '/'], "_"), client_server_key)).unwrap();
+        let sub = ctx.socket(zmq::SUB).unwrap();
+        keys.apply(&sub).unwrap();
+        sub.set_subscribe(b"").unwrap();
+        sub.set_rcvtimeo(100).unwrap();
+        sub.connect(endpoint).unwrap();
+
+        // PUB descarta hasta que termina el handshake: se reintenta un rato
+        for _ in 0..20 {
+            publisher.send("hola", 0).unwrap();
+            if let Ok(msg) = sub.recv_bytes(0) {
+                return Some(msg);
+            }
+        }
+        None
+    }
+
+    // Sin libsodium no hay handshake que probar: sólo que apply() no cae a texto plano
+    fn curve_available() -> bool {
+        if zmq::has("curve") == Some(true) {
+            return true;
+        }
+        let keys = CurveKeys::from_file(&keys_file("no-curve", SERVER_PUBLIC)).unwrap();
+        let sub = zmq::Context::new().socket(zmq::SUB).unwrap();
+        assert!(keys.apply(&sub).unwrap_err().contains("sin soporte CURVE"));
+        eprintln!("libzmq sin CURVE: se omite el handshake");
+        false
+    }
+
+    #[test]
+    fn curve_handshake_with_the_right_server_key() {
+        if curve_available() {
+            assert_eq!(handshake("inproc://curve-ok", SERVER_PUBLIC).as_deref(), Some(&b"hola"[..]));
+        }
+    }
+
+    #[test]
+    fn wrong_server_key_never_delivers() {
+        if curve_available() {
+            assert_eq!(handshake("inproc://curve-wrong", CLIENT_PUBLIC), None);
+        }
+    }
+}
@@ -0,0 +1,223 @@
+// Campos de envelope de cada mensaje del broker (top-level o dentro de "envelope")
+// y el filtro que descarta duplicados, secuencias viejas y comandos vencidos.
+//
+//   { "envelope": { "msg_id": "a1", "seq": 42, "source": "pos-gw", "boot_id": "b7", "issued_at": 1712345678901, "ttl_ms": 5000 }, "cmd": {...} }
+//
+// boot_id identifica el arranque del emisor: si cambia, su seq puede volver a empezar.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use chrono::DateTime;
+use serde_json::Value;
+
+const SEEN_CAPACITY: usize = 1024;
+const DEFAULT_SOURCE: &str = "broker";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Envelope {
+    pub msg_id: Option<String>,
+    pub seq: Option<u64>,
+    pub source: Option<String>,
+    pub boot_id: Option<String>,
+    pub issued_at_millis: Option<i64>,
+    pub ttl_ms: Option<i64>,
+}
+
+// issued_at: epoch en millis o RFC 3339 ("2024-04-05T12:00:00Z")
+fn parse_timestamp(v: &Value) -> Option<i64> {
+    match v {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => DateTime::parse_from_rfc3339(s).ok().map(|t| t.timestamp_millis()),
+        _ => None,
+    }
+}
+
+impl Envelope {
+    pub fn from_value(v: &Value) -> Self {
+        let env = v.get("envelope");
+        // primero el objeto envelope, después top-level
+        let field = |key: &str| env.and_then(|e| e.get(key)).or_else(|| v.get(key));
+        let str_at = |x: Option<&Value>| x.and_then(|s| s.as_str()).map(str::to_string);
+
+        Self {
+            msg_id: str_at(field("msg_id"))
+                .or_else(|| str_at(v.get("id")))
+                .or_else(|| str_at(v.get("cmd").and_then(|c| c.get("id")))),
+            seq: field("seq").and_then(|s| s.as_u64()),
+            source: str_at(field("source")),
+            boot_id: str_at(field("boot_id")),
+            issued_at_millis: field("issued_at").and_then(parse_timestamp),
+            ttl_ms: field("ttl_ms").and_then(|t| t.as_i64()),
+        }
+    }
+
+    pub fn source_key(&self) -> &str {
+        self.source.as_deref().unwrap_or(DEFAULT_SOURCE)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rejection {
+    Duplicate,
+    OutOfOrder { last: u64, got: u64 },
+    Expired { age_ms: i64, ttl_ms: i64 },
+}
+
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rejection::Duplicate => write!(f, "msg_id ya procesado"),
+            Rejection::OutOfOrder { last, got } => write!(f, "seq {got} <= último aplicado {last}"),
+            Rejection::Expired { age_ms, ttl_ms } => write!(f, "vencido: {age_ms}ms de antigüedad (ttl {ttl_ms}ms)"),
+        }
+    }
+}
+
+// Lo último aceptado de cada source
+#[derive(Debug, Clone)]
+struct SourceSession {
+    seq: u64,
+    boot_id: Option<String>,
+    issued_at_millis: Option<i64>,
+}
+
+impl SourceSession {
+    // ¿El mensaje viene de un arranque nuevo del emisor? Sin esto un seq 0/1 viejo
+    // (repetido por la red o por un tercero) reiniciaría la secuencia.
+    fn is_restart(&self, env: &Envelope, got: u64) -> bool {
+        let newer = match (env.issued_at_millis, self.issued_at_millis) {
+            (Some(issued), Some(last)) => Some(issued > last),
+            _ => None,
+        };
+        let new_boot = env.boot_id.is_some() && env.boot_id != self.boot_id;
+        // boot_id distinto alcanza, salvo que el timestamp diga que es anterior a lo aceptado
+        (new_boot && newer != Some(false)) || (got <= 1 && newer == Some(true))
+    }
+}
+
+// msg_ids recientes (LRU acotado) y sesión (seq, boot_id, issued_at) por source
+#[derive(Debug, Default)]
+pub struct MessageGate {
+    seen_order: VecDeque<String>,
+    seen: HashSet<String>,
+    sessions: HashMap<String, SourceSession>,
+}
+
+impl MessageGate {
+    // Si el mensaje pasa queda registrado (msg_id visto, seq como último de su source)
+    pub fn admit(&mut self, env: &Envelope, now_millis: i64) -> Result<(), Rejection> {
+        if env.msg_id.as_ref().is_some_and(|id| self.seen.contains(id)) {
+            return Err(Rejection::Duplicate);
+        }
+        if let (Some(issued), Some(ttl)) = (env.issued_at_millis, env.ttl_ms) {
+            let age_ms = now_millis - issued;
+            if age_ms > ttl {
+                return Err(Rejection::Expired { age_ms, ttl_ms: ttl });
+            }
+        }
+        if let Some(got) = env.seq {
+            let key = env.source_key();
+            let mut boot_id = env.boot_id.clone();
+            let mut issued_at_millis = env.issued_at_millis;
+            if let Some(prev) = self.sessions.get(key) {
+                if prev.is_restart(env, got) {
+                    if got <= prev.seq {
+                        eprintln!("[ZMQ] source '{key}' reinició su secuencia ({} → {got})", prev.seq);
+                    }
+                } else if got <= prev.seq {
+                    return Err(Rejection::OutOfOrder { last: prev.seq, got });
+                } else {
+                    // misma sesión: se conservan boot_id / timestamp si este mensaje no los trae
+                    boot_id = boot_id.or_else(|| prev.boot_id.clone());
+                    issued_at_millis = issued_at_millis.max(prev.issued_at_millis);
+                }
+            }
+            self.sessions.insert(key.to_string(), SourceSession { seq: got, boot_id, issued_at_millis });
+        }
+        if let Some(id) = &env.msg_id {
+            self.remember(id.clone());
+        }
+        Ok(())
+    }
+
+    fn remember(&mut self, id: String) {
+        if self.seen_order.len() >= SEEN_CAPACITY {
+            if let Some(old) = self.seen_order.pop_front() {
+                self.seen.remove(&old);
+            }
+        }
+        self.seen.insert(id.clone());
+        self.seen_order.push_back(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const NOW: i64 = 1_712_345_678_901;
+
+    fn env(v: Value) -> Envelope {
+        Envelope::from_value(&json!({ "envelope": v }))
+    }
+
+    #[test]
+    fn reads_envelope_object_before_top_level() {
+        let e = Envelope::from_value(&json!({
+            "envelope": { "msg_id": "a1", "seq": 42, "boot_id": "b7", "issued_at": "2024-04-05T19:34:38.901Z" },
+            "seq": 7, "source": "pos-gw", "ttl_ms": 5000
+        }));
+        assert_eq!(e.msg_id.as_deref(), Some("a1"));
+        assert_eq!(e.seq, Some(42));
+        assert_eq!(e.source_key(), "pos-gw");
+        assert_eq!(e.boot_id.as_deref(), Some("b7"));
+        assert_eq!(e.issued_at_millis, Some(NOW));
+        assert_eq!(e.ttl_ms, Some(5000));
+        assert_eq!(Envelope::default().source_key(), DEFAULT_SOURCE);
+    }
+
+    #[test]
+    fn duplicates_and_expired_are_rejected() {
+        let mut gate = MessageGate::default();
+        assert_eq!(gate.admit(&env(json!({ "msg_id": "a" })), NOW), Ok(()));
+        assert_eq!(gate.admit(&env(json!({ "msg_id": "a" })), NOW), Err(Rejection::Duplicate));
+        let old = env(json!({ "msg_id": "b", "issued_at": NOW - 6000, "ttl_ms": 5000 }));
+        assert_eq!(gate.admit(&old, NOW), Err(Rejection::Expired { age_ms: 6000, ttl_ms: 5000 }));
+    }
+
+    #[test]
+    fn replayed_low_seq_is_not_a_restart() {
+        let mut gate = MessageGate::default();
+        assert!(gate.admit(&env(json!({ "seq": 1, "issued_at": NOW - 300 })), NOW).is_ok());
+        assert!(gate.admit(&env(json!({ "seq": 5, "issued_at": NOW - 100 })), NOW).is_ok());
+        // el mismo seq 1 (o uno sin timestamp) repetido después no reinicia nada
+        let replay = env(json!({ "seq": 1, "issued_at": NOW - 300 }));
+        assert_eq!(gate.admit(&replay, NOW), Err(Rejection::OutOfOrder { last: 5, got: 1 }));
+        assert_eq!(gate.admit(&env(json!({ "seq": 0 })), NOW), Err(Rejection::OutOfOrder { last: 5, got: 0 }));
+        assert_eq!(gate.admit(&env(json!({ "seq": 5 })), NOW), Err(Rejection::OutOfOrder { last: 5, got: 5 }));
+    }
+
+    #[test]
+    fn restart_needs_a_new_session() {
+        let mut gate = MessageGate::default();
+        assert!(gate.admit(&env(json!({ "seq": 9, "boot_id": "b1", "issued_at": NOW - 500 })), NOW).is_ok());
+        // timestamp posterior al último aceptado
+        assert!(gate.admit(&env(json!({ "seq": 1, "boot_id": "b1", "issued_at": NOW - 400 })), NOW).is_ok());
+        assert!(gate.admit(&env(json!({ "seq": 4, "boot_id": "b1", "issued_at": NOW - 300 })), NOW).is_ok());
+        // boot_id nuevo, con cualquier seq
+        assert!(gate.admit(&env(json!({ "seq": 3, "boot_id": "b2", "issued_at": NOW - 200 })), NOW).is_ok());
+        // boot_id viejo con timestamp anterior: repetición
+        let stale = env(json!({ "seq": 2, "boot_id": "b1", "issued_at": NOW - 450 }));
+        assert_eq!(gate.admit(&stale, NOW), Err(Rejection::OutOfOrder { last: 3, got: 2 }));
+    }
+
+    #[test]
+    fn sequences_are_per_source() {
+        let mut gate = MessageGate::default();
+        assert!(gate.admit(&env(json!({ "seq": 10, "source": "a" })), NOW).is_ok());
+        assert!(gate.admit(&env(json!({ "seq": 2, "source": "b" })), NOW).is_ok());
+        assert!(gate.admit(&env(json!({ "seq": 11, "source": "a" })), NOW).is_ok());
+        assert!(gate.admit(&env(json!({ "seq": 2, "source": "a" })), NOW).is_err());
+    }
+}
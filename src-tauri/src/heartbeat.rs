@@ -0,0 +1,130 @@
+// Heartbeat real hacia el backend (HTTP POST con reqwest).
+// El resultado queda en AppState: last_hb_millis (último OK) y ack_init_snapshot (¿responde?).
+
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use serde::Serialize;
+use tokio::time::sleep;
+
+use crate::checksum::fnv1a64_hex;
+use crate::layout::UiLayout;
+use crate::state::{AppState, BrokerConnState};
+
+#[derive(Debug, Serialize)]
+struct HeartbeatPayload {
+    device_id: String,
+    app_version: &'static str,
+    uptime_secs: u64,
+    layout_hash: String,
+    last_message_millis: Option<i64>,
+    broker_state: BrokerConnState,
+    sent_at_millis: i64,
+}
+
+pub fn layout_hash(layout: &UiLayout) -> String {
+    fnv1a64_hex(layout.to_json().as_bytes())
+}
+
+fn build_payload(state: &AppState, device_id: &str, started: Instant) -> HeartbeatPayload {
+    let last_msg = state.get_last_message_millis();
+    HeartbeatPayload {
+        device_id: device_id.to_string(),
+        app_version: env!("CARGO_PKG_VERSION"),
+        uptime_secs: started.elapsed().as_secs(),
+        layout_hash: layout_hash(&state.current_layout.lock().unwrap()),
+        last_message_millis: (last_msg > 0).then_some(last_msg),
+        broker_state: state.get_broker_status().state,
+        sent_at_millis: Utc::now().timestamp_millis(),
+    }
+}
+
+async fn send_once(client: &reqwest::Client, url: &str, timeout: Duration, payload: &HeartbeatPayload) -> Result<(), String> {
+    let resp = client.post(url).timeout(timeout).json(payload).send().await.map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    Ok(())
+}
+
+pub async fn heartbeat_loop(state: AppState) {
+    let config = state.config();
+    let device_id = config.device_id();
+
+    let client = match reqwest::Client::builder().build() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("[HB] no se pudo crear el cliente HTTP: {e}");
+            return;
+        }
+    };
+    let started = Instant::now();
+    eprintln!("[HB] enviando heartbeat a {} cada {}s", config.heartbeat.url, config.heartbeat.interval_secs);
+
+    loop {
+        // se relee en cada vuelta: el config.update del broker sólo puede cambiar interval_secs
+        // y timeout_secs (BROKER_UPDATABLE); la URL viene de la config local / env / CLI
+        let hb = state.config().heartbeat;
+        let payload = build_payload(&state, &device_id, started);
+        match send_once(&client, &hb.url, Duration::from_secs(hb.timeout_secs), &payload).await {
+            Ok(()) => state.record_heartbeat(true, payload.sent_at_millis),
+            Err(e) => {
+                eprintln!("[HB] ❌ heartbeat fallido: {e}");
+                state.record_heartbeat(false, payload.sent_at_millis);
+            }
+        }
+        sleep(Duration::from_secs(hb.interval_secs)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{column, layout, text};
+
+    fn state() -> AppState {
+        AppState::new(layout(column().child(text("t", "inicio"))))
+    }
+
+    #[test]
+    fn payload_reflects_the_state() {
+        let st = state();
+        let p = build_payload(&st, "pos-1", Instant::now());
+        assert_eq!(p.device_id, "pos-1");
+        assert_eq!(p.layout_hash, layout_hash(&st.current_layout.lock().unwrap()));
+        assert_eq!(p.last_message_millis, None); // todavía no llegó nada del broker
+        assert_eq!(p.broker_state, BrokerConnState::Down);
+
+        st.mark_message_received();
+        st.apply_layout(layout(column().child(text("t", "otro"))), "m1").unwrap();
+        let q = build_payload(&st, "pos-1", Instant::now());
+        assert_eq!(q.last_message_millis, Some(st.get_last_message_millis()));
+        assert_ne!(q.layout_hash, p.layout_hash);
+
+        let v = serde_json::to_value(&q).unwrap();
+        assert_eq!(v["broker_state"], "down");
+        assert_eq!(v["app_version"], env!("CARGO_PKG_VERSION"));
+        assert!(v["sent_at_millis"].as_i64().unwrap() > 0);
+    }
+
+    #[test]
+    fn hash_ignores_data_context() {
+        // el hash es del layout guardado (con plantillas), no de lo que ve el front
+        let st = AppState::new(layout(column().child(text("t", "Hola {{msr.name}}"))));
+        let before = build_payload(&st, "pos-1", Instant::now()).layout_hash;
+        st.set_data("msr.name", "ANA".into());
+        assert_eq!(build_payload(&st, "pos-1", Instant::now()).layout_hash, before);
+    }
+
+    #[test]
+    fn record_heartbeat_keeps_the_last_ok() {
+        let st = state();
+        st.record_heartbeat(true, 1_000);
+        assert!(*st.ack_init_snapshot.lock().unwrap());
+        assert_eq!(*st.last_hb_millis.lock().unwrap(), 1_000);
+
+        st.record_heartbeat(false, 2_000);
+        assert!(!*st.ack_init_snapshot.lock().unwrap());
+        assert_eq!(*st.last_hb_millis.lock().unwrap(), 1_000);
+    }
+}
@@ -0,0 +1,157 @@
+// Historial acotado de layouts aplicados, para deshacer remotamente (ui.rollback)
+// un layout que validó pero que no era el correcto.
+
+use std::collections::VecDeque;
+
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::heartbeat::layout_hash;
+use crate::layout::{StyleDocument, UiLayout};
+
+const CAPACITY: usize = 20;
+
+#[derive(Debug, Clone)]
+pub struct LayoutRevision {
+    pub version: u64,
+    pub applied_at_millis: i64,
+    // msg_id del broker, "ui:<evento>", "startup" / "restored"
+    pub source: String,
+    pub screen_id: Option<String>,
+    pub layout: UiLayout,
+    // style cacheado en ese momento, para que ui.screen.show siga funcionando tras un rollback
+    pub style: Option<StyleDocument>,
+}
+
+// Lo que ve el front en list_layout_history (sin el layout completo)
+#[derive(Debug, Clone, Serialize)]
+pub struct RevisionSummary {
+    pub version: u64,
+    pub applied_at_millis: i64,
+    pub source: String,
+    pub screen_id: Option<String>,
+    pub layout_hash: String,
+    pub current: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct LayoutHistory {
+    entries: VecDeque<LayoutRevision>,
+    next_version: u64,
+}
+
+impl LayoutHistory {
+    pub fn record(
+        &mut self,
+        layout: UiLayout,
+        source: &str,
+        screen_id: Option<String>,
+        style: Option<StyleDocument>,
+    ) -> u64 {
+        self.next_version += 1;
+        if self.entries.len() >= CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(LayoutRevision {
+            version: self.next_version,
+            applied_at_millis: Utc::now().timestamp_millis(),
+            source: source.to_string(),
+            screen_id,
+            layout,
+            style,
+        });
+        self.next_version
+    }
+
+    // Deshacer: la revisión elegida vuelve a ser la actual y las posteriores se descartan.
+    // steps=1 → la anterior a la actual; version=N → esa revisión exacta.
+    pub fn rollback(&mut self, steps: Option<u64>, version: Option<u64>) -> Result<LayoutRevision, String> {
+        let idx = match (version, steps) {
+            (Some(v), _) => self
+                .entries
+                .iter()
+                .position(|r| r.version == v)
+                .ok_or_else(|| format!("la versión {v} no está en el historial (hay: {:?})", self.versions()))?,
+            (None, steps) => {
+                // steps viene del broker: cualquier u64, sin overflow ni panic con el mutex tomado
+                let steps = steps.unwrap_or(1);
+                if steps == 0 {
+                    return Err("steps debe ser >= 1".to_string());
+                }
+                usize::try_from(steps)
+                    .ok()
+                    .and_then(|s| s.checked_add(1))
+                    .and_then(|back| self.entries.len().checked_sub(back))
+                    .ok_or_else(|| format!("no hay {steps} revisiones anteriores (hay {})", self.entries.len().saturating_sub(1)))?
+            }
+        };
+        self.entries.truncate(idx + 1);
+        Ok(self.entries[idx].clone())
+    }
+
+    pub fn summaries(&self) -> Vec<RevisionSummary> {
+        let last = self.entries.back().map(|r| r.version);
+        self.entries
+            .iter()
+            .rev()
+            .map(|r| RevisionSummary {
+                version: r.version,
+                applied_at_millis: r.applied_at_millis,
+                source: r.source.clone(),
+                screen_id: r.screen_id.clone(),
+                layout_hash: layout_hash(&r.layout),
+                current: Some(r.version) == last,
+            })
+            .collect()
+    }
+
+    fn versions(&self) -> Vec<u64> {
+        self.entries.iter().map(|r| r.version).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{column, layout, text};
+
+    fn history(n: usize) -> LayoutHistory {
+        let mut h = LayoutHistory::default();
+        for i in 0..n {
+            h.record(layout(column().child(text("t", format!("v{i}")))), &format!("m{i}"), None, None);
+        }
+        h
+    }
+
+    #[test]
+    fn rollback_steps_bounds() {
+        let mut h = history(3);
+        assert_eq!(h.rollback(Some(0), None).unwrap_err(), "steps debe ser >= 1");
+        // steps = len: no hay tantas anteriores
+        assert_eq!(h.rollback(Some(3), None).unwrap_err(), "no hay 3 revisiones anteriores (hay 2)");
+        assert!(h.rollback(Some(u64::MAX), None).is_err());
+        assert_eq!(h.versions(), [1, 2, 3]);
+
+        assert_eq!(h.rollback(Some(2), None).unwrap().version, 1);
+        assert_eq!(h.versions(), [1]);
+        assert!(h.rollback(None, None).is_err());
+    }
+
+    #[test]
+    fn rollback_to_version_drops_later_ones() {
+        let mut h = history(4);
+        assert_eq!(h.rollback(None, Some(2)).unwrap().source, "m1");
+        assert_eq!(h.versions(), [1, 2]);
+        assert!(h.rollback(None, Some(9)).unwrap_err().contains("[1, 2]"));
+        assert_eq!(h.record(layout(column().child(text("t", "n"))), "m9", None, None), 5);
+    }
+
+    #[test]
+    fn keeps_the_last_capacity_entries() {
+        let h = history(CAPACITY + 3);
+        assert_eq!(h.versions().first(), Some(&4));
+        let summaries = h.summaries();
+        assert_eq!(summaries.len(), CAPACITY);
+        assert!(summaries[0].current && !summaries[1].current);
+    }
+}
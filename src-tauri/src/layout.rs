@@ -0,0 +1,406 @@
+// Modelo tipado del layout; espejo de src/ui/types.ts (UiLayout / UiNode).
+// Si cambias algo aquí, actualiza también el .ts del front.
+// build.rs incluye este archivo tal cual para generar el JSON Schema:
+// no debe depender de otros módulos del crate.
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, RootSchema, Schema, SchemaObject, StringValidation};
+use schemars::JsonSchema;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+
+// #RGB, #RRGGBB o #RRGGBBAA (lo mismo que exige validate::is_valid_color)
+pub const COLOR_PATTERN: &str = "^#([0-9a-fA-F]{3}|[0-9a-fA-F]{6}|[0-9a-fA-F]{8})$";
+
+fn color_schema(_gen: &mut SchemaGenerator) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        string: Some(Box::new(StringValidation {
+            pattern: Some(COLOR_PATTERN.to_string()),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+// Tamaños en px: el front los tipa como `number`, así que 12.0 u 8.5 también llegan;
+// se redondean al entero (lo que se serializa sigue siendo entero).
+fn lenient_px<'de, D: Deserializer<'de>>(d: D) -> Result<Option<u32>, D::Error> {
+    let Some(n) = Option::<f64>::deserialize(d)? else { return Ok(None) };
+    if !n.is_finite() || n < 0.0 || n > f64::from(u32::MAX) {
+        return Err(D::Error::custom(format!("se espera un número >= 0, llegó {n}")));
+    }
+    Ok(Some(n.round() as u32))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    Start,
+    Center,
+    End,
+    Stretch,
+}
+
+// Campos comunes a todos los nodos (BaseNode en el front)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct BaseNode {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub align: Option<Align>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visible_when_flag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visible_when: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TextNode {
+    #[serde(flatten)]
+    pub base: BaseNode,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "lenient_px")]
+    #[schemars(with = "Option<f64>")]
+    pub size: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bold: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "color_schema")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_from_input_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_template: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ButtonNode {
+    #[serde(flatten)]
+    pub base: BaseNode,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_click: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "color_schema")]
+    pub tint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "color_schema")]
+    pub text_color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enable_when_input_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enable_when_min_cents: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SpacerNode {
+    #[serde(flatten)]
+    pub base: BaseNode,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "lenient_px")]
+    #[schemars(with = "Option<f64>")]
+    pub height: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ScrollTextNode {
+    #[serde(flatten)]
+    pub base: BaseNode,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "color_schema")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "lenient_px")]
+    #[schemars(with = "Option<f64>")]
+    pub padding: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<f32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ColumnNode {
+    #[serde(flatten)]
+    pub base: BaseNode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "color_schema")]
+    pub background: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "lenient_px")]
+    #[schemars(with = "Option<f64>")]
+    pub padding: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "lenient_px")]
+    #[schemars(with = "Option<f64>")]
+    pub gap: Option<u32>,
+    #[schemars(length(min = 1))]
+    pub children: Vec<UiNode>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LogoNode {
+    #[serde(flatten)]
+    pub base: BaseNode,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "lenient_px")]
+    #[schemars(with = "Option<f64>")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "lenient_px")]
+    #[schemars(with = "Option<f64>")]
+    pub height: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct InputMoneyNode {
+    #[serde(flatten)]
+    pub base: BaseNode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct InputTextNode {
+    #[serde(flatten)]
+    pub base: BaseNode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct InputPasswordNode {
+    #[serde(flatten)]
+    pub base: BaseNode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+// Unión de nodos; el discriminante es "type" igual que en el front
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UiNode {
+    Text(TextNode),
+    Button(ButtonNode),
+    Spacer(SpacerNode),
+    Scroll(ScrollTextNode),
+    Column(ColumnNode),
+    Logo(LogoNode),
+    InputMoney(InputMoneyNode),
+    InputText(InputTextNode),
+    InputPassword(InputPasswordNode),
+}
+
+impl UiNode {
+    pub fn base(&self) -> &BaseNode {
+        match self {
+            UiNode::Text(n) => &n.base,
+            UiNode::Button(n) => &n.base,
+            UiNode::Spacer(n) => &n.base,
+            UiNode::Scroll(n) => &n.base,
+            UiNode::Column(n) => &n.base,
+            UiNode::Logo(n) => &n.base,
+            UiNode::InputMoney(n) => &n.base,
+            UiNode::InputText(n) => &n.base,
+            UiNode::InputPassword(n) => &n.base,
+        }
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.base().id.as_deref()
+    }
+
+    // mismo string que va en "type"
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            UiNode::Text(_) => "text",
+            UiNode::Button(_) => "button",
+            UiNode::Spacer(_) => "spacer",
+            UiNode::Scroll(_) => "scroll",
+            UiNode::Column(_) => "column",
+            UiNode::Logo(_) => "logo",
+            UiNode::InputMoney(_) => "input_money",
+            UiNode::InputText(_) => "input_text",
+            UiNode::InputPassword(_) => "input_password",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CustomerDisplay {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "lenient_px")]
+    #[schemars(with = "Option<f64>")]
+    pub size: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub align: Option<Align>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub use_logo: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "color_schema")]
+    pub bg_color: Option<String>,
+}
+
+// Tamaño del logo; el front lo lee de __style_logo_meta
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LogoMeta {
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "lenient_px")]
+    #[schemars(with = "Option<f64>")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "lenient_px")]
+    #[schemars(with = "Option<f64>")]
+    pub height: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct UiLayout {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "color_schema")]
+    pub background: Option<String>,
+    pub root: UiNode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub customer_display: Option<CustomerDisplay>,
+    // logo que viene en el style (LogoNode lo pinta desde window.__lastLayout)
+    #[serde(rename = "__style_logo_base64", default, skip_serializing_if = "Option::is_none")]
+    pub style_logo_base64: Option<String>,
+    #[serde(rename = "__style_logo_meta", default, skip_serializing_if = "Option::is_none")]
+    pub style_logo_meta: Option<LogoMeta>,
+}
+
+impl UiLayout {
+    pub fn to_json(&self) -> String {
+        // sólo tipos serializables sin mapas con claves no-string: no puede fallar
+        serde_json::to_string(self).expect("UiLayout siempre serializa")
+    }
+}
+
+// --------------------- documento style (ui.style.apply) ---------------------
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct StyleLogo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base64: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "lenient_px")]
+    #[schemars(with = "Option<f64>")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "lenient_px")]
+    #[schemars(with = "Option<f64>")]
+    pub height: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct StyleScreen {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub children: Vec<UiNode>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct StyleDocument {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "color_schema")]
+    pub background: Option<String>,
+    pub screens: Vec<StyleScreen>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub customer_display: Option<CustomerDisplay>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logo: Option<StyleLogo>,
+}
+
+impl StyleDocument {
+    // Arma el layout de una pantalla del style (la primera si no se pide ninguna)
+    pub fn to_layout(&self, screen_id: Option<&str>) -> Option<UiLayout> {
+        let bg = self.background.clone().unwrap_or_else(|| "#129ADA".to_string());
+        let pick = match screen_id {
+            Some(id) => self.screens.iter().find(|s| s.id.as_deref() == Some(id)),
+            None => self.screens.first(),
+        }?;
+
+        let mut layout = UiLayout {
+            background: Some(bg.clone()),
+            root: UiNode::Column(ColumnNode {
+                background: Some(bg),
+                padding: Some(24),
+                gap: Some(12),
+                children: pick.children.clone(),
+                ..Default::default()
+            }),
+            customer_display: self.customer_display.clone(),
+            style_logo_base64: None,
+            style_logo_meta: None,
+        };
+
+        if let Some(logo) = &self.logo {
+            if let Some(lg) = &logo.base64 {
+                layout.style_logo_base64 = Some(lg.clone());
+                layout.style_logo_meta = Some(LogoMeta { width: logo.width, height: logo.height });
+            }
+        }
+
+        Some(layout)
+    }
+
+    pub fn screen_ids(&self) -> Vec<&str> {
+        self.screens.iter().filter_map(|s| s.id.as_deref()).collect()
+    }
+}
+
+// --------------------- JSON Schema del protocolo ---------------------
+// Lo usan los productores de ui.apply / ui.style.apply para validar antes de publicar.
+pub fn layout_schema() -> RootSchema {
+    schemars::schema_for!(UiLayout)
+}
+
+pub fn style_schema() -> RootSchema {
+    schemars::schema_for!(StyleDocument)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn px_fields_accept_floats() {
+        let v = json!({
+            "root": { "type": "column", "padding": 8.5, "gap": 12.0, "children": [
+                { "type": "text", "text": "hola", "size": 22 },
+                { "type": "spacer", "height": 12.0 }
+            ]},
+            "customer_display": { "text": "x", "size": 24.0 }
+        });
+        let layout: UiLayout = serde_json::from_value(v).unwrap();
+        let UiNode::Column(col) = &layout.root else { panic!("se esperaba column") };
+        assert_eq!((col.padding, col.gap), (Some(9), Some(12)));
+        assert!(matches!(&col.children[0], UiNode::Text(t) if t.size == Some(22)));
+        assert!(matches!(&col.children[1], UiNode::Spacer(s) if s.height == Some(12)));
+        assert_eq!(layout.customer_display.unwrap().size, Some(24));
+        // se sigue serializando entero
+        let out = serde_json::to_value(&layout.root).unwrap();
+        assert_eq!(out["gap"], json!(12));
+    }
+
+    #[test]
+    fn px_fields_reject_negative_and_accept_null() {
+        let bad = json!({ "type": "spacer", "height": -4.0 });
+        assert!(serde_json::from_value::<UiNode>(bad).is_err());
+        let null = json!({ "type": "spacer", "height": null });
+        assert!(matches!(serde_json::from_value::<UiNode>(null).unwrap(), UiNode::Spacer(s) if s.height.is_none()));
+    }
+
+    #[test]
+    fn schema_types_px_as_number() {
+        let schema = serde_json::to_value(layout_schema()).unwrap();
+        assert_eq!(schema["definitions"]["CustomerDisplay"]["properties"]["size"]["type"], json!(["number", "null"]));
+        let text_node = &schema["definitions"]["UiNode"]["oneOf"][0]["properties"];
+        assert_eq!(text_node["size"]["type"], json!(["number", "null"]));
+    }
+}
@@ -1,77 +1,40 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+pub mod layout;
+pub mod builder;
 mod state;
+mod template;
+mod validate;
 mod broker; // 👈 añade el módulo del listener
+mod heartbeat;
+mod ack;
+mod patch;
+mod checksum;
+mod persist;
+mod history;
+mod envelope;
+mod signature;
+mod curve;
+mod config;
+mod commands;
+mod upstream;
+mod msr;
+mod track;
+mod redact;
+mod payment;
+mod processor;
+mod screens;
 
+use std::collections::HashMap;
+
+use payment::{PaymentEvent, PaymentState};
+use screens::{build_base_layout, build_payment_layout, build_start_layout};
 use state::AppState;
+use upstream::{route_event, EventRoute, UiEvent};
 use broker::start_zmq_listener; // 👈 importa la función
 
-use tauri::{AppHandle, Wry, Emitter};
-
-use std::time::Duration;
-use tokio::time::sleep;
-use chrono::Utc;
-
-// =====================
-// 1. Generadores de layout (igual a tu versión)
-// =====================
-
-fn build_base_layout() -> String {
-    let json = r###"
-{
-  "background": "#FFFFFF",
-  "root": {
-    "type": "column",
-    "background": "#FFFFFF",
-    "padding": 24,
-    "gap": 12,
-    "children": [
-      { "type": "text", "id": "txt_title", "text": "Inicio", "align": "center", "size": 22, "bold": true, "color": "#111827" },
-      { "type": "spacer", "id": "sp_start_1", "height": 12 },
-      { "type": "button", "id": "btn_proceed", "text": "Proceder al cobro", "on_click": "go_payment", "align": "center", "tint": "#2962FF", "text_color": "#FFFFFF", "enabled": true }
-    ]
-  },
-  "customer_display": { "text": "Bienvenido", "size": 24, "align": "center", "use_logo": true, "bg_color": "#000000" }
-}
-"###;
-    json.to_string()
-}
-
-fn build_payment_layout(reading: bool, last_result: &str) -> String {
-    let (rc_text, rc_click, rc_tint) = if reading {
-        ("Cancelar lectura", "btn_cancel_msr", "#DC2626")
-    } else {
-        ("Leer banda magnética", "btn_read_msr", "#D97706")
-    };
-    let escaped_result = last_result.replace('"', "\\\"");
-
-    let raw = format!(r###"{{
-  "background": "#FFFFFF",
-  "root": {{
-    "type": "column",
-    "background": "#FFFFFF",
-    "padding": 24,
-    "gap": 8,
-    "children": [
-      {{ "type": "button", "id": "btn_back", "text": "Regresar", "on_click": "nav_back", "align": "start", "tint": "#111827", "text_color": "#FFFFFF", "icon": "back" }},
-      {{ "type": "spacer", "id": "sp_pay_2", "height": 8 }},
-      {{ "type": "button", "id": "btn_print", "text": "Imprimir mensaje", "on_click": "print_from_button", "align": "center", "tint": "#2563EB", "text_color": "#FFFFFF", "icon": "print" }},
-      {{ "type": "button", "id": "btn_read_cancel", "text": "{rc_text}", "on_click": "{rc_click}", "align": "center", "tint": "{rc_tint}", "text_color": "#FFFFFF", "icon": "info" }},
-      {{ "type": "spacer", "id": "sp_pay_1", "height": 12 }},
-      {{ "type": "text", "id": "txt_result_title", "text": "Resultado de lectura", "align": "start", "size": 14, "bold": true }},
-      {{ "type": "text", "id": "msr_name", "text": "Nombre: {{msr.cardholderName}}", "align": "start", "size": 12 }},
-      {{ "type": "text", "id": "msr_t1", "text": "Track 1: {{msr.track1}}", "align": "start", "size": 12 }},
-      {{ "type": "text", "id": "msr_t2", "text": "Track 2: {{msr.track2}}", "align": "start", "size": 12 }},
-      {{ "type": "text", "id": "msr_t3", "text": "Track 3: {{msr.track3}}", "align": "start", "size": 12 }},
-      {{ "type": "scroll", "id": "msr_result", "weight": 1, "padding": 12, "text": "{escaped_result}" }}
-    ]
-  }},
-  "customer_display": {{ "text": "Bienvenido", "size": 24, "align": "center", "use_logo": true, "bg_color": "#000000" }}
-}}"###,
-        rc_text = rc_text, rc_click = rc_click, rc_tint = rc_tint, escaped_result = escaped_result,
-    );
-    raw.replace('\'', "\"")
-}
+use tauri::{AppHandle, Wry, Emitter, Manager};
+
 
 fn emit_layout_update(app: &AppHandle<Wry>, json: &str) {
     let _ = app.emit("layout_update", json.to_string());
@@ -82,11 +45,92 @@ fn get_ui_layout(state: tauri::State<AppState>) -> Result<String, String> {
     Ok(state.get_layout())
 }
 
+// Actualiza un valor del data-context ({{path}} en los textos) y re-emite si se ve en pantalla
 #[tauri::command]
-fn on_ui_event(
-    event_id: String,
+fn set_ui_data(
+    path: String,
+    value: serde_json::Value,
     state: tauri::State<AppState>,
     app: tauri::AppHandle
+) -> Result<(), String> {
+    if state.set_data(&path, value) {
+        emit_layout_update(&app, &state.get_layout());
+    }
+    Ok(())
+}
+
+// Revisiones de layout aplicadas, de la más nueva a la más vieja (ui.rollback usa `version`)
+#[tauri::command]
+fn list_layout_history(state: tauri::State<AppState>) -> Result<Vec<history::RevisionSummary>, String> {
+    Ok(state.list_history())
+}
+
+// Config efectiva (archivo + perfil + env + CLI), sólo lectura
+#[tauri::command]
+fn get_config(state: tauri::State<AppState>) -> Result<config::AppConfig, String> {
+    Ok(state.config())
+}
+
+// ----- control del listener ZMQ -----
+// El join espera a que el hilo vea la señal (hasta un recv_timeout): fuera del hilo de IPC
+#[tauri::command]
+async fn broker_stop(state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || broker::stop_zmq_listener(&state))
+        .await
+        .map_err(|e| format!("no se pudo detener el listener: {e}"))?
+}
+
+#[tauri::command]
+fn broker_start(state: tauri::State<AppState>, app: tauri::AppHandle) -> Result<(), String> {
+    start_zmq_listener(app, state.inner().clone())
+}
+
+// Cambia el endpoint SUB: si el listener corre reabre la sesión, si no queda para el próximo broker_start
+#[tauri::command]
+fn broker_set_endpoint(endpoint: String, state: tauri::State<AppState>) -> Result<(), String> {
+    state
+        .update_config(serde_json::json!({ "broker": { "sub_endpoint": endpoint } }))
+        .map(|_| ())
+        .map_err(|errors| errors.join("; "))
+}
+
+#[tauri::command]
+fn get_broker_status(state: tauri::State<AppState>) -> Result<state::BrokerStatus, String> {
+    Ok(state.get_broker_status())
+}
+
+// JSON Schema del protocolo (mismo que deja build.rs en gen/schemas)
+#[tauri::command]
+fn get_protocol_schema(document: Option<String>) -> Result<serde_json::Value, String> {
+    let schema = match document.as_deref().unwrap_or("layout") {
+        "layout" => layout::layout_schema(),
+        "style" => layout::style_schema(),
+        other => return Err(format!("documento desconocido: {other} (usa 'layout' o 'style')")),
+    };
+    serde_json::to_value(schema).map_err(|e| e.to_string())
+}
+
+// Request/reply con el backend (autorizar, consultar): espera la respuesta o el timeout
+#[tauri::command]
+async fn broker_request(
+    method: String,
+    params: Option<serde_json::Value>,
+    timeout_ms: Option<u64>,
+    state: tauri::State<'_, AppState>
+) -> Result<serde_json::Value, String> {
+    let timeout = timeout_ms.map(std::time::Duration::from_millis);
+    state.request(&method, params.unwrap_or(serde_json::Value::Null), timeout).await
+}
+
+// async: los handlers pueden esperar al backend con `state.request(...).await`
+// (sin bloquear el hilo principal mientras llega la respuesta)
+#[tauri::command]
+async fn on_ui_event(
+    event_id: String,
+    inputs: Option<HashMap<String, String>>,
+    state: tauri::State<'_, AppState>,
+    app: tauri::AppHandle
 ) -> Result<Option<String>, String> {
     // ⬇️ Ignorar nav_to:* (navegación local en el front)
     if event_id.starts_with("nav_to:") {
@@ -94,68 +138,294 @@ fn on_ui_event(
         return Ok(None);
     }
 
-    let mut new_layout: Option<String> = None;
+    // botones del flujo de cobro → eventos de la máquina de estados
+    let payment_event = match event_id.as_str() {
+        "go_payment" | "btn_proceed" => Some(PaymentEvent::Start),
+        "btn_read_msr" => Some(PaymentEvent::ReadCard { amount: inputs.as_ref().and_then(|i| i.get("pay_amount").cloned()) }),
+        "btn_cancel_msr" => Some(PaymentEvent::CancelRead),
+        "print_from_button" => Some(PaymentEvent::Print),
+        "nav_back" => Some(PaymentEvent::Back),
+        _ => None,
+    };
+
+    // ⬆️ lo no manejado aquí (y lo de upstream.forward_events) lo decide el backend;
+    // su respuesta llega por el DEALER como cualquier mensaje del broker
+    let cfg = state.config();
+    match route_event(&event_id, payment_event.is_some(), &cfg.upstream.forward_events, state.upstream.is_enabled()) {
+        EventRoute::Forward => {
+            let screen = state.current_screen.lock().unwrap().clone();
+            let event = UiEvent::new(cfg.device_id(), screen, &event_id, inputs.unwrap_or_default());
+            if let Err(e) = state.upstream.send(event) {
+                eprintln!("[UI] no se pudo enviar {event_id} al backend: {e}");
+            }
+        }
+        EventRoute::Unhandled => eprintln!("Evento no manejado: {}", event_id),
+        EventRoute::Local => {}
+    }
+
+    let Some(event) = payment_event else { return Ok(None) };
+    fire_payment(&app, &state, event)?;
+    Ok(Some(state.get_layout()))
+}
+
+// ----- flujo de cobro (ver payment.rs) -----
+// Aplica el evento y hace lo que corresponde al estado al que se entró.
+// Un botón rechazado deja la pantalla como estaba, con el motivo en el área de resultado.
+fn fire_payment(app: &AppHandle, state: &AppState, event: PaymentEvent) -> Result<(), String> {
+    let from_ui = event.is_ui();
+    let (before, result, snapshot) = {
+        let mut machine = state.payment.lock().unwrap();
+        let before = machine.state().name();
+        let result = machine.apply(event);
+        (before, result, machine.snapshot())
+    };
+    match result {
+        Ok(false) => return Ok(()), // timer viejo
+        Ok(true) => {}
+        Err(reason) => {
+            eprintln!("[PAY] ❌ {reason}");
+            if from_ui && !matches!(snapshot.state, PaymentState::Idle) {
+                render_payment(app, state, &snapshot.state, &reason);
+            }
+            return Err(reason);
+        }
+    }
+
+    let now = snapshot.state.name();
+    if before == "awaiting_card" && now != "authorizing" {
+        cancel_msr(state); // cancelar / regresar con la lectura en curso
+    }
+    if now == "idle" || (now == "amount_entry" && before == "idle") {
+        state.set_data("msr", serde_json::Value::Null); // cobro nuevo: sin la tarjeta anterior
+    }
+    if now == "awaiting_card" {
+        tauri::async_runtime::spawn(read_card(app.clone(), state.clone()));
+    }
+    if let Some(wait) = snapshot.state.timeout(&state.config().payment) {
+        let (app, state, epoch) = (app.clone(), state.clone(), snapshot.epoch);
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(wait).await;
+            let _ = fire_payment(&app, &state, PaymentEvent::Timeout { epoch });
+        });
+    }
+    // timers, lector y autorizador no pisan una pantalla que no es la del cobro
+    if from_ui || state.showing_payment() {
+        render_payment(app, state, &snapshot.state, &snapshot.message);
+    }
+    Ok(())
+}
 
-    match event_id.as_str() {
-        "go_payment" | "btn_proceed" => {
-            let reading = state.get_reading();
-            let last_result = "— sin lectura aún —";
-            new_layout = Some(build_payment_layout(reading, last_result));
+fn render_payment(app: &AppHandle, state: &AppState, payment: &PaymentState, message: &str) {
+    let candidate = match payment {
+        PaymentState::Idle => build_start_layout(message),
+        other => build_payment_layout(other, message),
+    };
+    if let Err(violations) = state.apply_layout(candidate, &format!("payment:{}", payment.name())) {
+        eprintln!("[UI] layout rechazado: {}", validate::describe(&violations));
+        state.restore_last_good();
+    }
+    emit_layout_update(app, &state.get_layout());
+}
+
+// Cobro con el procesador configurado (processor.rs): authorize + capture.
+// Si la máquina ya no lo espera (timeout de authorizing, regresar) se anula lo aprobado.
+async fn authorize(app: AppHandle, state: AppState, amount: payment::Amount, card: track::MaskedCard) {
+    let processor = match state.processor.get() {
+        Ok(p) => p,
+        Err(e) => {
+            let _ = fire_payment(&app, &state, PaymentEvent::Declined { reason: e.to_string() });
+            return;
+        }
+    };
+    let device_id = state.config().device_id();
+    let request = processor::AuthRequest {
+        reference: format!("{device_id}-pay-{}", chrono::Utc::now().timestamp_millis()),
+        device_id,
+        amount_cents: amount.cents,
+        card,
+    };
+    eprintln!("[PAY] autorizando $ {amount} ({}, ref {})", processor.kind(), request.reference);
+    let event = match processor::sale(processor.as_ref(), request).await {
+        Ok(auth) if auth.approved => PaymentEvent::Approved {
+            auth_code: auth.auth_code.unwrap_or_else(|| "—".to_string()),
+            transaction_id: auth.transaction_id.unwrap_or_default(),
+        },
+        Ok(auth) => PaymentEvent::Declined { reason: auth.reason.unwrap_or_else(|| "rechazado por el emisor".to_string()) },
+        Err(e) => PaymentEvent::Declined { reason: e.to_string() },
+    };
+    let late = match &event {
+        PaymentEvent::Approved { transaction_id, .. } => Some(transaction_id.clone()),
+        _ => None,
+    };
+    if let (Err(_), Some(txn)) = (fire_payment(&app, &state, event), late) {
+        eprintln!("[PAY] aprobación tardía de {txn}: se anula");
+        if let Err(e) = processor.void(txn.clone()).await {
+            eprintln!("[PAY] ❌ no se pudo anular {txn}: {e}");
         }
-        "nav_back" => new_layout = Some(build_base_layout()),
-        "btn_read_msr" => { state.set_reading(true); new_layout = Some(build_payment_layout(true, "Leyendo banda magnética.")); }
-        "btn_cancel_msr" => { state.set_reading(false); new_layout = Some(build_payment_layout(false, "Lectura cancelada por el usuario")); }
-        "print_from_button" => { let reading = state.get_reading(); new_layout = Some(build_payment_layout(reading, "Enviando a impresora.")); }
-        other => eprintln!("Evento no manejado: {}", other),
     }
+}
+
+// Anulación / devolución de un cobro ya hecho (transaction_id de la pantalla de aprobado)
+#[tauri::command]
+async fn payment_void(transaction_id: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let processor = state.processor.get().map_err(|e| e.to_string())?;
+    processor.void(transaction_id.clone()).await.map_err(|e| e.to_string())?;
+    eprintln!("[PAY] {transaction_id} anulada");
+    Ok(())
+}
+
+#[tauri::command]
+async fn payment_refund(transaction_id: String, amount: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let amount = payment::Amount::parse(&amount)?;
+    let processor = state.processor.get().map_err(|e| e.to_string())?;
+    processor.refund(transaction_id.clone(), amount.cents).await.map_err(|e| e.to_string())?;
+    eprintln!("[PAY] devolución de $ {amount} sobre {transaction_id}");
+    Ok(())
+}
+
+// Estado del cobro (el mismo que decide qué pantalla de pago se pinta)
+#[tauri::command]
+fn get_payment_state(state: tauri::State<AppState>) -> Result<payment::PaymentSnapshot, String> {
+    Ok(state.payment.lock().unwrap().snapshot())
+}
 
-    if let Some(candidate) = new_layout {
-        if state.apply_layout_safely(&candidate) {
-            emit_layout_update(&app, &candidate);
-            return Ok(Some(candidate));
-        } else {
-            state.restore_last_good();
-            let fallback = state.get_layout();
-            emit_layout_update(&app, &fallback);
-            return Ok(Some(fallback));
+// ----- lector de banda magnética (ver msr.rs) -----
+// El front escucha msr_state: con el lector wedge junta las teclas mientras reading = true
+fn emit_msr_state(app: &AppHandle<Wry>, reading: bool, reader: &str) {
+    let _ = app.emit("msr_state", serde_json::json!({ "reading": reading, "reader": reader }));
+}
+
+fn cancel_msr(state: &AppState) {
+    if let Ok(reader) = state.msr.get() {
+        reader.cancel();
+    }
+}
+
+// Una lectura completa (estado awaiting_card): deja msr.* en el data-context y pasa a autorizar
+async fn read_card(app: AppHandle, state: AppState) {
+    let timeout = std::time::Duration::from_secs(state.config().msr.read_timeout_secs);
+    let result = match state.msr.get() {
+        Ok(reader) => {
+            emit_msr_state(&app, true, reader.kind());
+            let result = msr::read_with_timeout(reader.as_ref(), timeout).await;
+            emit_msr_state(&app, false, reader.kind());
+            result
+        }
+        Err(e) => Err(e),
+    };
+    let event = match result {
+        Ok(swipe) => {
+            for warning in swipe.tracks.errors() {
+                eprintln!("[MSR] ⚠️ {warning}");
+            }
+            state.set_data("msr", swipe.to_data());
+            PaymentEvent::CardRead(swipe.tracks.masked())
+        }
+        // cancel_msr: la máquina ya salió de awaiting_card
+        Err(msr::MsrError::Cancelled) => return,
+        Err(e) => {
+            eprintln!("[MSR] {e}");
+            PaymentEvent::CardFailed(e.to_string())
         }
+    };
+    let card_read = matches!(event, PaymentEvent::CardRead(_));
+    if fire_payment(&app, &state, event).is_ok() && card_read {
+        let current = state.payment.lock().unwrap().state().clone();
+        let PaymentState::Authorizing { amount, card } = current else { return };
+        authorize(app, state, amount, card).await;
     }
-    Ok(None)
 }
 
+// Lector wedge: lo tecleado durante la lectura, hasta el Enter
+#[tauri::command]
+fn msr_wedge_input(data: String, state: tauri::State<AppState>) -> Result<(), String> {
+    let reader = state.msr.get().map_err(|e| e.to_string())?;
+    reader.feed(&data).map_err(|e| e.to_string())
+}
 
-// Opcional: heartbeat simulado (igual que tu versión)
-async fn heartbeat_ticker(_app: AppHandle<Wry>, state: AppState) {
-    loop {
-        let now = Utc::now().timestamp_millis();
-        state.set_status("tcp://34.70.157.148:5557", "http://34.70.157.148:8080/ack", true, now);
-        sleep(Duration::from_secs(15)).await;
+// Lector simulado: un swipe "%B...^...?;...?" como si se hubiera pasado la tarjeta
+#[tauri::command]
+fn msr_simulate_swipe(raw: String, state: tauri::State<AppState>) -> Result<(), String> {
+    let reader = state.msr.get().map_err(|e| e.to_string())?;
+    if reader.kind() != "simulated" {
+        return Err(format!("el lector configurado es {} (msr.reader = \"simulated\" para simular)", reader.kind()));
     }
+    reader.feed(&raw).map_err(|e| e.to_string())
 }
 
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     let initial_layout = build_base_layout();
-    let app_state = AppState::new(&initial_layout);
+    let app_state = AppState::new(initial_layout);
 
     tauri::Builder::default()
         .manage(app_state.clone())
-        .invoke_handler(tauri::generate_handler![ get_ui_layout, on_ui_event ])
+        .invoke_handler(tauri::generate_handler![ get_ui_layout, get_config, get_broker_status, broker_start, broker_stop, broker_set_endpoint, broker_request, list_layout_history, get_protocol_schema, set_ui_data, on_ui_event, msr_wedge_input, msr_simulate_swipe, get_payment_state, payment_void, payment_refund ])
         .setup(move |app| {
+            // 🔸 config: si no valida no se arranca (mejor que pintar pagos contra un endpoint equivocado)
+            let cli = config::parse_cli(std::env::args().skip(1))?;
+            let config_dir = app.path().app_config_dir()?;
+            let cfg = config::load(&config_dir, &cli).map_err(|errors| {
+                for e in &errors {
+                    eprintln!("[CONFIG] ❌ {e}");
+                }
+                format!("config inválida: {}", errors.join("; "))
+            })?;
+            eprintln!(
+                "[CONFIG] perfil '{}' ({}), device_id {}",
+                cfg.profile,
+                cfg.source_file.as_deref().unwrap_or("sin archivo"),
+                cfg.device_id()
+            );
+            let features = cfg.features.clone();
+            *app_state.config.lock().unwrap() = cfg;
+
+            // 🔸 restaura el último estado de UI guardado (si no hay o está corrupto: layout base)
+            if features.persistence {
+                match app.path().app_data_dir() {
+                    Ok(dir) => {
+                        if app_state.enable_persistence(persist::state_file(&dir)) {
+                            emit_layout_update(app.handle(), &app_state.get_layout());
+                        }
+                    }
+                    Err(e) => eprintln!("[PERSIST] sin app data dir, no se persiste: {e}"),
+                }
+            }
+
             // 🔸 Arranca el listener ZMQ (aquí es donde “escucha y aplica”)
             {
                 let state_for_broker = app_state.clone();
                 let handle_for_broker = app.handle().clone();
-                start_zmq_listener(handle_for_broker, state_for_broker);
+                if let Err(e) = start_zmq_listener(handle_for_broker, state_for_broker) {
+                    eprintln!("[ZMQ] ❌ {e}");
+                }
             }
 
-            // 🔸 (Opcional) heartbeat
-            {
+            // 🔸 eventos de UI hacia el backend (sólo si upstream.endpoint está configurado)
+            upstream::start_upstream(app.handle().clone(), app_state.clone());
+
+            // 🔸 lector de banda magnética de la pantalla de pago
+            app_state.msr.install(msr::from_config(&app_state.config().msr));
+
+            // 🔸 autorizador de cobros (mock / http / broker)
+            match processor::from_config(&app_state.config(), &app_state.upstream) {
+                Ok(p) => app_state.processor.install(p),
+                Err(e) => eprintln!("[PAY] ❌ sin procesador: {e}"),
+            }
+
+            // 🔸 entrega de ACKs de cada mensaje del broker
+            if features.acks {
+                let state_for_ack = app_state.clone();
+                tauri::async_runtime::spawn(ack::ack_worker(state_for_ack));
+            } else {
+                app_state.acks.disable();
+            }
+
+            // 🔸 heartbeat real hacia el backend
+            if features.heartbeat {
                 let state_for_hb = app_state.clone();
-                let handle_for_hb = app.handle().clone();
-                tauri::async_runtime::spawn(async move {
-                    heartbeat_ticker(handle_for_hb, state_for_hb).await;
-                });
+                tauri::async_runtime::spawn(heartbeat::heartbeat_loop(state_for_hb));
             }
 
             // ❌ Quitamos el polling HTTP que pegaba a otra IP/endpoint no “pull”.
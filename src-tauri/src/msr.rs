@@ -0,0 +1,402 @@
+// Lector de banda magnética (MSR). La pantalla de pago sólo conoce MsrReader:
+//
+//  - wedge: el lector "teclea" la banda en la ventana; mientras hay una lectura activa el
+//    front junta las teclas (evento msr_state) y las manda con msr_wedge_input al Enter
+//  - serial: lee una línea del puerto msr.serial_port (se abre sólo durante la lectura)
+//  - simulated: swipes de msr.sim_file (uno por línea, en ronda, tras msr.sim_delay_ms)
+//    o, sin archivo, lo que llegue por msr_simulate_swipe
+//
+// Una lectura: start() devuelve el receiver del resultado; cancel() lo resuelve con
+// Cancelled. read_with_timeout junta las dos cosas para el que espera.
+
+use std::fmt;
+use std::io::Read;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::Value;
+use tokio::sync::oneshot;
+
+use crate::config::{MsrConfig, MsrKind};
+use crate::track::{self, ParsedSwipe};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Swipe {
+    // tracks ya validados (ver track.rs); el string crudo no se guarda
+    pub tracks: ParsedSwipe,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MsrError {
+    Busy,     // ya hay una lectura en curso
+    Timeout,
+    Cancelled,
+    Unreadable(String), // llegó algo pero no es una banda
+    Device(String),
+}
+
+impl fmt::Display for MsrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MsrError::Busy => write!(f, "ya hay una lectura en curso"),
+            MsrError::Timeout => write!(f, "no se pasó ninguna tarjeta"),
+            MsrError::Cancelled => write!(f, "lectura cancelada"),
+            MsrError::Unreadable(e) => write!(f, "banda ilegible: {e}"),
+            MsrError::Device(e) => write!(f, "lector: {e}"),
+        }
+    }
+}
+
+pub type SwipeResult = Result<Swipe, MsrError>;
+
+impl Swipe {
+    // "%B4111...^APELLIDO/NOMBRE^2512...?;4111...=2512...?" (como lo mandan wedge y serial).
+    // Basta con un track bueno; los errores de los demás quedan en tracks.errors().
+    pub fn parse(raw: &str) -> SwipeResult {
+        let tracks = track::parse(raw);
+        if tracks.is_empty() {
+            return Err(MsrError::Unreadable(if raw.trim().is_empty() { "vacía".to_string() } else { "sin tracks".to_string() }));
+        }
+        if !tracks.has_valid_track() {
+            return Err(MsrError::Unreadable(tracks.errors().join("; ")));
+        }
+        Ok(Swipe { tracks })
+    }
+
+    pub fn cardholder_name(&self) -> Option<String> {
+        self.tracks.cardholder_name()
+    }
+
+    // Lo que va a data-context bajo "msr" ({{msr.track1}}, {{msr.cardholderName}}, ...): enmascarado
+    pub fn to_data(&self) -> Value {
+        serde_json::to_value(self.tracks.masked()).unwrap_or(Value::Null)
+    }
+}
+
+pub trait MsrReader: Send + Sync {
+    fn kind(&self) -> &'static str;
+    fn start(&self) -> Result<oneshot::Receiver<SwipeResult>, MsrError>;
+    fn cancel(&self);
+    // wedge y simulated reciben la banda de afuera (front / comando); serial no
+    fn feed(&self, _raw: &str) -> Result<(), MsrError> {
+        Err(MsrError::Device(format!("el lector {} no acepta entrada externa", self.kind())))
+    }
+}
+
+// Espera un swipe; al vencer el timeout cancela la lectura en el lector
+pub async fn read_with_timeout(reader: &dyn MsrReader, timeout: Duration) -> SwipeResult {
+    let rx = reader.start()?;
+    match tokio::time::timeout(timeout, rx).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(_)) => Err(MsrError::Cancelled),
+        Err(_) => {
+            reader.cancel();
+            Err(MsrError::Timeout)
+        }
+    }
+}
+
+// ----- lectura en curso (compartida por los tres lectores) -----
+// El número de lectura evita que un hilo viejo (serial, simulado) conteste la siguiente.
+#[derive(Default)]
+struct ReadSlot {
+    current: Mutex<Option<(u64, oneshot::Sender<SwipeResult>)>>,
+    seq: AtomicU64,
+}
+
+impl ReadSlot {
+    fn open(&self) -> Result<(u64, oneshot::Receiver<SwipeResult>), MsrError> {
+        let mut current = self.current.lock().unwrap();
+        if current.as_ref().is_some_and(|(_, tx)| !tx.is_closed()) {
+            return Err(MsrError::Busy);
+        }
+        let id = self.seq.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        *current = Some((id, tx));
+        Ok((id, rx))
+    }
+
+    fn is_current(&self, id: u64) -> bool {
+        self.current.lock().unwrap().as_ref().is_some_and(|(cur, tx)| *cur == id && !tx.is_closed())
+    }
+
+    // None = la que esté en curso
+    fn finish(&self, id: Option<u64>, result: SwipeResult) -> bool {
+        let mut current = self.current.lock().unwrap();
+        let matches = match (current.as_ref(), id) {
+            (None, _) => false,
+            (Some((cur, _)), Some(id)) => *cur == id,
+            (Some(_), None) => true,
+        };
+        match current.take_if(|_| matches) {
+            Some((_, tx)) => tx.send(result).is_ok(),
+            None => false,
+        }
+    }
+}
+
+// --------------------- wedge (teclado) ---------------------
+#[derive(Default)]
+pub struct WedgeReader {
+    slot: ReadSlot,
+}
+
+impl MsrReader for WedgeReader {
+    fn kind(&self) -> &'static str {
+        "wedge"
+    }
+
+    fn start(&self) -> Result<oneshot::Receiver<SwipeResult>, MsrError> {
+        self.slot.open().map(|(_, rx)| rx)
+    }
+
+    fn cancel(&self) {
+        self.slot.finish(None, Err(MsrError::Cancelled));
+    }
+
+    // Lo tecleado entre el inicio de la lectura y el Enter
+    fn feed(&self, raw: &str) -> Result<(), MsrError> {
+        if !self.slot.finish(None, Swipe::parse(raw)) {
+            return Err(MsrError::Device("no hay una lectura en curso".to_string()));
+        }
+        Ok(())
+    }
+}
+
+// --------------------- serial ---------------------
+pub struct SerialReader {
+    port: String,
+    baud: u32,
+    slot: Arc<ReadSlot>,
+}
+
+impl SerialReader {
+    pub fn new(port: &str, baud: u32) -> Self {
+        Self { port: port.to_string(), baud, slot: Arc::new(ReadSlot::default()) }
+    }
+}
+
+impl MsrReader for SerialReader {
+    fn kind(&self) -> &'static str {
+        "serial"
+    }
+
+    fn start(&self) -> Result<oneshot::Receiver<SwipeResult>, MsrError> {
+        let (id, rx) = self.slot.open()?;
+        let (slot, port, baud) = (self.slot.clone(), self.port.clone(), self.baud);
+        std::thread::spawn(move || {
+            let result = read_serial_line(&port, baud, || slot.is_current(id));
+            if let Some(result) = result {
+                slot.finish(Some(id), result);
+            }
+        });
+        Ok(rx)
+    }
+
+    fn cancel(&self) {
+        self.slot.finish(None, Err(MsrError::Cancelled));
+    }
+}
+
+// Una línea (hasta CR/LF) del puerto; None = la lectura se canceló mientras tanto
+fn read_serial_line(port: &str, baud: u32, still_wanted: impl Fn() -> bool) -> Option<SwipeResult> {
+    let mut serial = match serialport::new(port, baud).timeout(Duration::from_millis(200)).open() {
+        Ok(s) => s,
+        Err(e) => return Some(Err(MsrError::Device(format!("no se pudo abrir {port}: {e}")))),
+    };
+    eprintln!("[MSR] {port} abierto ({baud} baudios)");
+    let mut line = Vec::new();
+    let mut buf = [0u8; 256];
+    while still_wanted() {
+        match serial.read(&mut buf) {
+            Ok(n) => {
+                line.extend_from_slice(&buf[..n]);
+                if let Some(end) = line.iter().position(|b| *b == b'\r' || *b == b'\n') {
+                    return Some(Swipe::parse(&String::from_utf8_lossy(&line[..end])));
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+            Err(e) => return Some(Err(MsrError::Device(format!("{port}: {e}")))),
+        }
+    }
+    None
+}
+
+// --------------------- simulado ---------------------
+pub struct SimulatedReader {
+    swipes: Vec<String>, // vacío = espera msr_simulate_swipe
+    next: AtomicUsize,
+    delay: Duration,
+    slot: Arc<ReadSlot>,
+}
+
+impl SimulatedReader {
+    pub fn new(sim_file: Option<&str>, delay: Duration) -> Self {
+        let swipes = match sim_file.map(std::fs::read_to_string) {
+            Some(Ok(txt)) => txt.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')).map(String::from).collect(),
+            Some(Err(e)) => {
+                eprintln!("[MSR] ❌ no se pudo leer msr.sim_file ({e}); se espera msr_simulate_swipe");
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+        Self { swipes, next: AtomicUsize::new(0), delay, slot: Arc::new(ReadSlot::default()) }
+    }
+}
+
+impl MsrReader for SimulatedReader {
+    fn kind(&self) -> &'static str {
+        "simulated"
+    }
+
+    fn start(&self) -> Result<oneshot::Receiver<SwipeResult>, MsrError> {
+        let (id, rx) = self.slot.open()?;
+        if !self.swipes.is_empty() {
+            let raw = self.swipes[self.next.fetch_add(1, Ordering::Relaxed) % self.swipes.len()].clone();
+            let (slot, delay) = (self.slot.clone(), self.delay);
+            std::thread::spawn(move || {
+                std::thread::sleep(delay);
+                slot.finish(Some(id), Swipe::parse(&raw));
+            });
+        }
+        Ok(rx)
+    }
+
+    fn cancel(&self) {
+        self.slot.finish(None, Err(MsrError::Cancelled));
+    }
+
+    fn feed(&self, raw: &str) -> Result<(), MsrError> {
+        if !self.slot.finish(None, Swipe::parse(raw)) {
+            return Err(MsrError::Device("no hay una lectura en curso".to_string()));
+        }
+        Ok(())
+    }
+}
+
+pub fn from_config(cfg: &MsrConfig) -> Arc<dyn MsrReader> {
+    match cfg.reader {
+        MsrKind::Wedge => Arc::new(WedgeReader::default()),
+        MsrKind::Serial => Arc::new(SerialReader::new(cfg.serial_port.as_deref().unwrap_or_default(), cfg.serial_baud)),
+        MsrKind::Simulated => Arc::new(SimulatedReader::new(cfg.sim_file.as_deref(), Duration::from_millis(cfg.sim_delay_ms))),
+    }
+}
+
+// Lector instalado en setup (según msr.reader)
+#[derive(Clone, Default)]
+pub struct MsrHandle {
+    reader: Arc<Mutex<Option<Arc<dyn MsrReader>>>>,
+}
+
+impl MsrHandle {
+    pub fn install(&self, reader: Arc<dyn MsrReader>) {
+        eprintln!("[MSR] lector {}", reader.kind());
+        *self.reader.lock().unwrap() = Some(reader);
+    }
+
+    pub fn get(&self) -> Result<Arc<dyn MsrReader>, MsrError> {
+        self.reader.lock().unwrap().clone().ok_or_else(|| MsrError::Device("sin lector configurado".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SWIPE: &str = "%B4111111111111111^DOE/JOHN^25121010000000000000?;4111111111111111=25121010000000000000?";
+
+    fn pan(result: SwipeResult) -> Option<String> {
+        result.unwrap().tracks.masked().pan
+    }
+
+    #[tokio::test]
+    async fn simulated_reads_from_file_in_turn() {
+        let path = std::env::temp_dir().join(format!("msr-sim-{}.txt", std::process::id()));
+        std::fs::write(&path, format!("# tarjetas de prueba\n{SWIPE}\n\n;5555555555554444=2612101?\n")).unwrap();
+        let reader = SimulatedReader::new(path.to_str(), Duration::from_millis(5));
+        let wait = Duration::from_secs(2);
+        assert_eq!(pan(read_with_timeout(&reader, wait).await).as_deref(), Some("411111******1111"));
+        assert_eq!(pan(read_with_timeout(&reader, wait).await).as_deref(), Some("555555******4444"));
+        assert_eq!(pan(read_with_timeout(&reader, wait).await).as_deref(), Some("411111******1111"));
+    }
+
+    #[tokio::test]
+    async fn simulated_without_file_waits_for_feed() {
+        let reader = SimulatedReader::new(None, Duration::ZERO);
+        assert!(reader.feed(SWIPE).is_err()); // sin lectura en curso
+        let rx = reader.start().unwrap();
+        reader.feed(SWIPE).unwrap();
+        assert_eq!(rx.await.unwrap().unwrap().cardholder_name().as_deref(), Some("JOHN DOE"));
+
+        let rx = reader.start().unwrap();
+        reader.feed("basura").unwrap();
+        assert!(matches!(rx.await.unwrap(), Err(MsrError::Unreadable(_))));
+    }
+
+    #[tokio::test]
+    async fn second_start_is_busy_and_cancel_resolves() {
+        let reader = WedgeReader::default();
+        let rx = reader.start().unwrap();
+        assert_eq!(reader.start().err(), Some(MsrError::Busy));
+        reader.cancel();
+        assert_eq!(rx.await.unwrap(), Err(MsrError::Cancelled));
+        // libre otra vez
+        assert!(reader.start().is_ok());
+    }
+
+    // Wedge que cuenta los cancel()
+    #[derive(Default)]
+    struct Spy {
+        inner: WedgeReader,
+        cancels: AtomicUsize,
+    }
+
+    impl MsrReader for Spy {
+        fn kind(&self) -> &'static str {
+            "spy"
+        }
+        fn start(&self) -> Result<oneshot::Receiver<SwipeResult>, MsrError> {
+            self.inner.start()
+        }
+        fn cancel(&self) {
+            self.cancels.fetch_add(1, Ordering::Relaxed);
+            self.inner.cancel();
+        }
+    }
+
+    #[tokio::test]
+    async fn timeout_cancels_the_reader() {
+        let reader = Spy::default();
+        assert_eq!(read_with_timeout(&reader, Duration::from_millis(20)).await, Err(MsrError::Timeout));
+        assert_eq!(reader.cancels.load(Ordering::Relaxed), 1);
+        assert!(reader.start().is_ok());
+    }
+
+    #[tokio::test]
+    async fn stale_read_id_is_ignored() {
+        let slot = ReadSlot::default();
+        let (old, rx_old) = slot.open().unwrap();
+        assert!(slot.finish(None, Err(MsrError::Cancelled)));
+        assert_eq!(rx_old.await.unwrap(), Err(MsrError::Cancelled));
+
+        let (new, rx_new) = slot.open().unwrap();
+        assert_ne!(old, new);
+        assert!(!slot.is_current(old));
+        // el hilo de la lectura anterior contesta tarde: no llega a la nueva
+        assert!(!slot.finish(Some(old), Swipe::parse(SWIPE)));
+        assert!(slot.is_current(new));
+        assert!(slot.finish(Some(new), Err(MsrError::Timeout)));
+        assert_eq!(rx_new.await.unwrap(), Err(MsrError::Timeout));
+    }
+
+    #[test]
+    fn swipe_parse_errors() {
+        assert_eq!(Swipe::parse("  "), Err(MsrError::Unreadable("vacía".to_string())));
+        assert_eq!(Swipe::parse("hola"), Err(MsrError::Unreadable("sin tracks".to_string())));
+        assert_eq!(Swipe::parse("%E?;E?"), Err(MsrError::Unreadable("track 1: no leído; track 2: no leído".to_string())));
+        let data = Swipe::parse(SWIPE).unwrap().to_data();
+        assert_eq!(data["pan"], "411111******1111");
+        assert!(!data.to_string().contains("4111111111111111"));
+    }
+}
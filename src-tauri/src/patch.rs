@@ -0,0 +1,166 @@
+// ui.update incremental: RFC 6902 (JSON Patch) o updates por id de nodo,
+// aplicados sobre el layout actual en forma de JSON.
+//
+//   { "patch":   [ { "op": "replace", "path": "/root/children/0/text", "value": "Hola" } ] }
+//   { "id": "txt_title", "set": { "text": "Hola" } }
+//   { "updates": [ { "id": "txt_title", "set": { "text": "Hola" } }, ... ] }
+
+use serde_json::Value;
+
+pub fn apply_json_patch(doc: &mut Value, ops: &Value) -> Result<(), String> {
+    let patch: json_patch::Patch =
+        serde_json::from_value(ops.clone()).map_err(|e| format!("patch inválido: {e}"))?;
+    json_patch::patch(doc, &patch).map_err(|e| format!("no se pudo aplicar el patch: {e}"))
+}
+
+// Busca un nodo por id dentro del árbol (root + children recursivos)
+fn find_node_mut<'a>(node: &'a mut Value, id: &str) -> Option<&'a mut Value> {
+    if node.get("id").and_then(|v| v.as_str()) == Some(id) {
+        return Some(node);
+    }
+    node.get_mut("children")?
+        .as_array_mut()?
+        .iter_mut()
+        .find_map(|child| find_node_mut(child, id))
+}
+
+// Cada update: { "id": "...", "set": { campo: valor } }; valor null borra el campo
+pub fn apply_node_updates(doc: &mut Value, updates: &[Value]) -> Result<(), String> {
+    for (i, update) in updates.iter().enumerate() {
+        let id = update
+            .get("id")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| format!("updates[{i}]: falta 'id'"))?;
+        let set = update
+            .get("set")
+            .and_then(|v| v.as_object())
+            .ok_or_else(|| format!("updates[{i}]: falta 'set' (objeto)"))?;
+
+        let root = doc.get_mut("root").ok_or("el layout no tiene root")?;
+        let node = find_node_mut(root, id)
+            .and_then(|n| n.as_object_mut())
+            .ok_or_else(|| format!("updates[{i}]: no existe un nodo con id '{id}'"))?;
+        for (key, value) in set {
+            if value.is_null() {
+                node.remove(key);
+            } else {
+                node.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    Ok(())
+}
+
+// ¿args de ui.update traen un update incremental? (si no, es un reemplazo completo)
+pub fn is_incremental(args: &Value) -> bool {
+    args.get("patch").is_some() || args.get("updates").is_some() || args.get("set").is_some()
+}
+
+pub fn apply_update_args(doc: &mut Value, args: &Value) -> Result<(), String> {
+    if let Some(ops) = args.get("patch") {
+        apply_json_patch(doc, ops)?;
+    }
+    if let Some(updates) = args.get("updates") {
+        let list = updates.as_array().ok_or("'updates' debe ser un arreglo")?;
+        apply_node_updates(doc, list)?;
+    }
+    if args.get("set").is_some() {
+        apply_node_updates(doc, std::slice::from_ref(args))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{button, column, layout, text};
+    use crate::state::AppState;
+    use serde_json::json;
+
+    fn doc() -> Value {
+        serde_json::to_value(layout(
+            column().child(text("title", "Hola").bold()).child(column().child(button("ok", "Aceptar").tint("#00f"))),
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn rfc6902_ops() {
+        let mut d = doc();
+        let ops = json!([
+            { "op": "test", "path": "/root/children/0/text", "value": "Hola" },
+            { "op": "replace", "path": "/root/children/0/text", "value": "Chau" },
+            { "op": "add", "path": "/root/children/0/color", "value": "#fff" },
+            { "op": "remove", "path": "/root/children/0/bold" },
+        ]);
+        apply_json_patch(&mut d, &ops).unwrap();
+        assert_eq!(d["root"]["children"][0], json!({ "type": "text", "id": "title", "text": "Chau", "color": "#fff" }));
+    }
+
+    #[test]
+    fn failing_test_op_leaves_doc_untouched() {
+        let mut d = doc();
+        let ops = json!([
+            { "op": "replace", "path": "/root/children/0/text", "value": "Chau" },
+            { "op": "test", "path": "/root/children/0/text", "value": "Hola" },
+        ]);
+        let err = apply_json_patch(&mut d, &ops).unwrap_err();
+        assert!(err.starts_with("no se pudo aplicar el patch"), "{err}");
+        assert_eq!(d, doc());
+
+        assert!(apply_json_patch(&mut d, &json!([{ "op": "mover" }])).unwrap_err().starts_with("patch inválido"));
+    }
+
+    #[test]
+    fn set_by_id_reaches_nested_nodes() {
+        let mut d = doc();
+        apply_update_args(&mut d, &json!({ "id": "ok", "set": { "text": "Pagar", "enabled": false } })).unwrap();
+        let ok = &d["root"]["children"][1]["children"][0];
+        assert_eq!(ok["text"], "Pagar");
+        assert_eq!(ok["enabled"], false);
+        assert_eq!(ok["tint"], "#00f");
+    }
+
+    #[test]
+    fn updates_list_and_null_removes() {
+        let mut d = doc();
+        let args = json!({ "updates": [
+            { "id": "title", "set": { "text": "Total", "bold": null } },
+            { "id": "ok", "set": { "tint": null } },
+        ]});
+        assert!(is_incremental(&args));
+        apply_update_args(&mut d, &args).unwrap();
+        assert_eq!(d["root"]["children"][0], json!({ "type": "text", "id": "title", "text": "Total" }));
+        assert!(d["root"]["children"][1]["children"][0].get("tint").is_none());
+    }
+
+    #[test]
+    fn unknown_id_is_an_error() {
+        let mut d = doc();
+        let args = json!({ "updates": [{ "id": "title", "set": { "text": "x" } }, { "id": "nope", "set": { "text": "y" } }] });
+        assert_eq!(apply_update_args(&mut d, &args).unwrap_err(), "updates[1]: no existe un nodo con id 'nope'");
+        assert_eq!(apply_node_updates(&mut d, &[json!({ "id": "title" })]).unwrap_err(), "updates[0]: falta 'set' (objeto)");
+        assert!(!is_incremental(&json!({ "root": {} })));
+    }
+
+    #[test]
+    fn invalid_result_keeps_current_and_last_good() {
+        let st = AppState::new(layout(column().child(text("t", "inicio"))));
+        let before = st.current_layout.lock().unwrap().clone();
+
+        // color inválido y id inexistente: ninguno llega a current
+        let bad = json!({ "id": "t", "set": { "color": "rojo" } });
+        assert!(st.update_layout("m1", |d| apply_update_args(d, &bad)).is_err());
+        let missing = json!({ "id": "x", "set": { "text": "y" } });
+        assert!(st.update_layout("m2", |d| apply_update_args(d, &missing)).is_err());
+        assert_eq!(*st.current_layout.lock().unwrap(), before);
+        assert_eq!(*st.last_good_layout.lock().unwrap(), before);
+        assert_eq!(st.list_history().len(), 1);
+
+        let good = json!({ "id": "t", "set": { "text": "listo" } });
+        st.update_layout("m3", |d| apply_update_args(d, &good)).unwrap();
+        let expected = layout(column().child(text("t", "listo")));
+        assert_eq!(*st.current_layout.lock().unwrap(), expected);
+        assert_eq!(*st.last_good_layout.lock().unwrap(), expected);
+    }
+}
@@ -0,0 +1,359 @@
+// Máquina de estados del cobro (reemplaza el bool `reading`):
+//
+//   idle → amount_entry → awaiting_card → authorizing → approved → receipt → idle
+//               ↑   cancelar / error de lectura  │           └→ declined → (reintentar) awaiting_card
+//               └────────────────────────────────┘
+//
+// Botones, lector y autorizador sólo mandan PaymentEvent; lo que no corresponde al
+// estado actual se rechaza sin tocar nada. Los efectos (arrancar el lector, autorizar,
+// pintar la pantalla) los hace lib.rs según el estado al que se entró.
+// Cada estado puede tener timeout (payment.* en la config); cada transición sube
+// `epoch`, así un timer de un estado anterior ya no hace nada.
+
+use std::fmt;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::config::PaymentConfig;
+use crate::track::MaskedCard;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Amount {
+    pub cents: u64,
+}
+
+impl Amount {
+    // "12.50", "12,5", "$ 1250" (sin separador de miles)
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let cleaned: String = input.chars().filter(|c| !c.is_whitespace() && *c != '$').collect();
+        let (int, frac) = cleaned.split_once(['.', ',']).unwrap_or((&cleaned, ""));
+        let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if int.is_empty() || !all_digits(int) || !all_digits(frac) || frac.len() > 2 {
+            return Err(format!("monto inválido: '{}'", input.trim()));
+        }
+        let frac_cents = format!("{frac:0<2}").parse::<u64>().unwrap_or(0);
+        let cents = int
+            .parse::<u64>()
+            .ok()
+            .and_then(|units| units.checked_mul(100))
+            .and_then(|c| c.checked_add(frac_cents))
+            .ok_or_else(|| format!("monto fuera de rango: '{}'", input.trim()))?;
+        if cents == 0 {
+            return Err("el monto debe ser mayor que cero".to_string());
+        }
+        Ok(Self { cents })
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{:02}", self.cents / 100, self.cents % 100)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum PaymentState {
+    Idle,
+    // el monto queda si se vuelve acá tras cancelar o fallar la lectura
+    AmountEntry { amount: Option<Amount> },
+    AwaitingCard { amount: Amount },
+    Authorizing { amount: Amount, card: MaskedCard },
+    Approved { amount: Amount, card: MaskedCard, auth_code: String, transaction_id: String },
+    Declined { amount: Amount, reason: String },
+    Receipt { amount: Amount, auth_code: String },
+}
+
+impl PaymentState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PaymentState::Idle => "idle",
+            PaymentState::AmountEntry { .. } => "amount_entry",
+            PaymentState::AwaitingCard { .. } => "awaiting_card",
+            PaymentState::Authorizing { .. } => "authorizing",
+            PaymentState::Approved { .. } => "approved",
+            PaymentState::Declined { .. } => "declined",
+            PaymentState::Receipt { .. } => "receipt",
+        }
+    }
+
+    pub fn amount(&self) -> Option<Amount> {
+        match self {
+            PaymentState::Idle => None,
+            PaymentState::AmountEntry { amount } => *amount,
+            PaymentState::AwaitingCard { amount }
+            | PaymentState::Authorizing { amount, .. }
+            | PaymentState::Approved { amount, .. }
+            | PaymentState::Declined { amount, .. }
+            | PaymentState::Receipt { amount, .. } => Some(*amount),
+        }
+    }
+
+    // Cuánto puede quedarse en este estado sin que pase nada
+    pub fn timeout(&self, cfg: &PaymentConfig) -> Option<Duration> {
+        let secs = match self {
+            PaymentState::AmountEntry { .. } => cfg.amount_entry_secs,
+            PaymentState::Authorizing { .. } => cfg.authorizing_secs,
+            PaymentState::Approved { .. } | PaymentState::Declined { .. } => cfg.result_secs,
+            PaymentState::Receipt { .. } => cfg.receipt_secs,
+            // awaiting_card: manda el timeout del lector (msr.read_timeout_secs)
+            PaymentState::Idle | PaymentState::AwaitingCard { .. } => return None,
+        };
+        Some(Duration::from_secs(secs))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PaymentEvent {
+    // botones
+    Start,
+    ReadCard { amount: Option<String> }, // lo escrito en pay_amount
+    CancelRead,
+    Print,
+    Back,
+    // lector / autorizador / timers
+    CardRead(MaskedCard),
+    CardFailed(String),
+    Approved { auth_code: String, transaction_id: String },
+    Declined { reason: String },
+    Timeout { epoch: u64 },
+}
+
+impl PaymentEvent {
+    // Lo que viene de un botón: si se rechaza, el usuario tiene que ver por qué
+    pub fn is_ui(&self) -> bool {
+        matches!(
+            self,
+            PaymentEvent::Start | PaymentEvent::ReadCard { .. } | PaymentEvent::CancelRead | PaymentEvent::Print | PaymentEvent::Back
+        )
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            PaymentEvent::Start => "iniciar cobro",
+            PaymentEvent::ReadCard { .. } => "leer tarjeta",
+            PaymentEvent::CancelRead => "cancelar lectura",
+            PaymentEvent::Print => "imprimir",
+            PaymentEvent::Back => "regresar",
+            PaymentEvent::CardRead(_) => "tarjeta leída",
+            PaymentEvent::CardFailed(_) => "error de lectura",
+            PaymentEvent::Approved { .. } => "aprobación",
+            PaymentEvent::Declined { .. } => "rechazo",
+            PaymentEvent::Timeout { .. } => "timeout",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PaymentSnapshot {
+    #[serde(flatten)]
+    pub state: PaymentState,
+    pub message: String, // lo que muestra el área de resultado
+    pub epoch: u64,
+}
+
+#[derive(Debug)]
+pub struct PaymentMachine {
+    state: PaymentState,
+    message: String,
+    epoch: u64,
+}
+
+impl Default for PaymentMachine {
+    fn default() -> Self {
+        Self { state: PaymentState::Idle, message: "— sin lectura aún —".to_string(), epoch: 0 }
+    }
+}
+
+impl PaymentMachine {
+    pub fn state(&self) -> &PaymentState {
+        &self.state
+    }
+
+    pub fn snapshot(&self) -> PaymentSnapshot {
+        PaymentSnapshot { state: self.state.clone(), message: self.message.clone(), epoch: self.epoch }
+    }
+
+    // La pantalla de cobro ya no está (la reemplazó el broker): vuelve a idle sin pasar por
+    // las transiciones e invalida los timers pendientes. false = ya estaba en idle.
+    pub fn reset(&mut self) -> bool {
+        if matches!(self.state, PaymentState::Idle) {
+            return false;
+        }
+        *self = Self { epoch: self.epoch + 1, ..Self::default() };
+        true
+    }
+
+    // Ok(true) = hubo transición; Ok(false) = timer de un estado anterior (se ignora).
+    // Err = no permitido en este estado: no cambia nada.
+    pub fn apply(&mut self, event: PaymentEvent) -> Result<bool, String> {
+        use PaymentEvent as E;
+        use PaymentState as S;
+
+        if let E::Timeout { epoch } = event {
+            if epoch != self.epoch {
+                return Ok(false);
+            }
+        }
+        let from = self.state.name();
+        let (next, message) = match (&self.state, event) {
+            (S::Idle, E::Start) => (S::AmountEntry { amount: None }, "Ingrese el monto a cobrar".to_string()),
+
+            (S::AmountEntry { amount }, E::ReadCard { amount: input }) => {
+                let amount = match input.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+                    Some(raw) => Amount::parse(raw)?,
+                    None => amount.ok_or("ingrese el monto antes de leer la tarjeta")?,
+                };
+                (S::AwaitingCard { amount }, "Leyendo banda magnética.".to_string())
+            }
+            // reintento tras un rechazo: mismo monto
+            (S::Declined { amount, .. }, E::ReadCard { .. }) => {
+                (S::AwaitingCard { amount: *amount }, "Leyendo banda magnética.".to_string())
+            }
+
+            (S::AwaitingCard { amount }, E::CardRead(card)) => {
+                (S::Authorizing { amount: *amount, card }, "Autorizando…".to_string())
+            }
+            (S::AwaitingCard { amount }, E::CardFailed(e)) => {
+                (S::AmountEntry { amount: Some(*amount) }, format!("Error de lectura: {e}"))
+            }
+            (S::AwaitingCard { amount }, E::CancelRead) => {
+                (S::AmountEntry { amount: Some(*amount) }, "Lectura cancelada por el usuario".to_string())
+            }
+
+            (S::Authorizing { amount, card }, E::Approved { auth_code, transaction_id }) => {
+                let message = format!("Aprobado — código {auth_code}\nTransacción {transaction_id}");
+                (S::Approved { amount: *amount, card: card.clone(), auth_code, transaction_id }, message)
+            }
+            (S::Authorizing { amount, .. }, E::Declined { reason }) => {
+                let message = format!("Rechazado: {reason}");
+                (S::Declined { amount: *amount, reason }, message)
+            }
+            (S::Authorizing { amount, .. }, E::Timeout { .. }) => {
+                let reason = "sin respuesta del autorizador".to_string();
+                (S::Declined { amount: *amount, reason: reason.clone() }, format!("Rechazado: {reason}"))
+            }
+
+            (S::Approved { amount, auth_code, .. }, E::Print) => {
+                (S::Receipt { amount: *amount, auth_code: auth_code.clone() }, "Enviando a impresora.".to_string())
+            }
+
+            (S::Authorizing { .. }, E::Back) => return Err("no se puede salir mientras se autoriza el pago".to_string()),
+            (_, E::Back) => (S::Idle, String::new()),
+            (S::AmountEntry { .. } | S::Approved { .. } | S::Declined { .. } | S::Receipt { .. }, E::Timeout { .. }) => {
+                (S::Idle, "Tiempo agotado".to_string())
+            }
+
+            (_, event) => return Err(format!("acción no permitida: {} en {from}", event.describe())),
+        };
+        eprintln!("[PAY] {from} → {}", next.name());
+        self.state = next;
+        self.message = message;
+        self.epoch += 1;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card() -> MaskedCard {
+        MaskedCard { pan: Some("411111******1111".to_string()), ..Default::default() }
+    }
+
+    fn approved() -> PaymentEvent {
+        PaymentEvent::Approved { auth_code: "A1B2C3".to_string(), transaction_id: "mock-000001".to_string() }
+    }
+
+    fn run(machine: &mut PaymentMachine, events: Vec<PaymentEvent>) -> Vec<&'static str> {
+        events
+            .into_iter()
+            .map(|e| {
+                assert_eq!(machine.apply(e), Ok(true));
+                machine.state().name()
+            })
+            .collect()
+    }
+
+    fn read(amount: &str) -> PaymentEvent {
+        PaymentEvent::ReadCard { amount: Some(amount.to_string()) }
+    }
+
+    #[test]
+    fn happy_path_ends_at_receipt() {
+        let mut m = PaymentMachine::default();
+        let states = run(&mut m, vec![PaymentEvent::Start, read("12.50"), PaymentEvent::CardRead(card()), approved(), PaymentEvent::Print]);
+        assert_eq!(states, ["amount_entry", "awaiting_card", "authorizing", "approved", "receipt"]);
+        assert_eq!(m.state(), &PaymentState::Receipt { amount: Amount { cents: 1250 }, auth_code: "A1B2C3".to_string() });
+        let epoch = m.snapshot().epoch;
+        assert_eq!(m.apply(PaymentEvent::Timeout { epoch }), Ok(true));
+        assert_eq!(m.state(), &PaymentState::Idle);
+    }
+
+    #[test]
+    fn declined_then_retry_ends_at_receipt() {
+        let mut m = PaymentMachine::default();
+        let declined = PaymentEvent::Declined { reason: "fondos insuficientes".to_string() };
+        let states = run(&mut m, vec![PaymentEvent::Start, read("10"), PaymentEvent::CardRead(card()), declined]);
+        assert_eq!(states.last(), Some(&"declined"));
+        assert_eq!(m.snapshot().message, "Rechazado: fondos insuficientes");
+        // reintento con el mismo monto, sin volver a escribirlo
+        let states = run(&mut m, vec![PaymentEvent::ReadCard { amount: None }, PaymentEvent::CardRead(card()), approved(), PaymentEvent::Print]);
+        assert_eq!(states, ["awaiting_card", "authorizing", "approved", "receipt"]);
+        assert_eq!(m.state().amount(), Some(Amount { cents: 1000 }));
+    }
+
+    #[test]
+    fn illegal_transitions_change_nothing() {
+        let mut m = PaymentMachine::default();
+        assert_eq!(m.apply(PaymentEvent::CancelRead), Err("acción no permitida: cancelar lectura en idle".to_string()));
+        run(&mut m, vec![PaymentEvent::Start, read("5")]);
+        let before = m.snapshot();
+        assert_eq!(m.apply(read("5")), Err("acción no permitida: leer tarjeta en awaiting_card".to_string()));
+        assert_eq!(m.apply(PaymentEvent::Print), Err("acción no permitida: imprimir en awaiting_card".to_string()));
+        assert_eq!(m.apply(approved()), Err("acción no permitida: aprobación en awaiting_card".to_string()));
+        let after = m.snapshot();
+        assert_eq!((after.state, after.message, after.epoch), (before.state, before.message, before.epoch));
+    }
+
+    #[test]
+    fn amount_is_required_and_validated() {
+        let mut m = PaymentMachine::default();
+        run(&mut m, vec![PaymentEvent::Start]);
+        assert!(m.apply(PaymentEvent::ReadCard { amount: None }).is_err());
+        assert!(m.apply(read("12.345")).is_err());
+        assert!(m.apply(read("0")).is_err());
+        assert_eq!(m.state().name(), "amount_entry");
+        assert_eq!(Amount::parse("$ 1.250,5"), Err("monto inválido: '$ 1.250,5'".to_string()));
+        assert_eq!(Amount::parse("12,5").unwrap().to_string(), "12.50");
+    }
+
+    #[test]
+    fn back_is_rejected_while_authorizing() {
+        let mut m = PaymentMachine::default();
+        run(&mut m, vec![PaymentEvent::Start, read("20"), PaymentEvent::CardRead(card())]);
+        assert_eq!(m.apply(PaymentEvent::Back), Err("no se puede salir mientras se autoriza el pago".to_string()));
+        assert_eq!(m.state().name(), "authorizing");
+        // sin respuesta a tiempo: rechazado, y de ahí sí se puede salir
+        let epoch = m.snapshot().epoch;
+        run(&mut m, vec![PaymentEvent::Timeout { epoch }, PaymentEvent::Back]);
+        assert_eq!(m.state(), &PaymentState::Idle);
+    }
+
+    #[test]
+    fn stale_timeout_is_ignored() {
+        let mut m = PaymentMachine::default();
+        run(&mut m, vec![PaymentEvent::Start]);
+        let amount_entry_epoch = m.snapshot().epoch;
+        run(&mut m, vec![read("7"), PaymentEvent::CancelRead]);
+        // volvió a amount_entry, pero el timer viejo ya no cuenta
+        assert_eq!(m.state().name(), "amount_entry");
+        assert_eq!(m.apply(PaymentEvent::Timeout { epoch: amount_entry_epoch }), Ok(false));
+        assert_eq!(m.state().name(), "amount_entry");
+        let epoch = m.snapshot().epoch;
+        assert_eq!(m.apply(PaymentEvent::Timeout { epoch }), Ok(true));
+        assert_eq!(m.snapshot().message, "Tiempo agotado");
+    }
+}
@@ -0,0 +1,119 @@
+// Persistencia del estado de UI en el app data dir (state.json).
+// Escritura atómica (tmp + rename) y checksum para detectar archivos corruptos;
+// si algo no cuadra se arranca con el layout incorporado.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::checksum::fnv1a64_hex;
+use crate::layout::{StyleDocument, UiLayout};
+use crate::redact;
+use crate::validate;
+
+const FILE_NAME: &str = "state.json";
+const FORMAT_VERSION: u32 = 1;
+
+// Lo que sobrevive a un reinicio (el logo viaja dentro del layout y del style)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PersistedState {
+    pub current_layout: UiLayout,
+    pub last_good_layout: UiLayout,
+    #[serde(default)]
+    pub style: Option<StyleDocument>,
+    #[serde(default)]
+    pub screen_id: Option<String>,
+    pub saved_at_millis: i64,
+}
+
+// En disco: el payload va como string para que el checksum cubra exactamente esos bytes
+#[derive(Serialize, Deserialize)]
+struct StateFile {
+    version: u32,
+    checksum: String,
+    payload: String,
+}
+
+pub fn state_file(dir: &Path) -> PathBuf {
+    dir.join(FILE_NAME)
+}
+
+pub fn save(path: &Path, state: &PersistedState) -> Result<(), String> {
+    // a disco no va ningún dato de tarjeta (p.ej. el resultado de lectura dentro del layout)
+    let mut payload = serde_json::to_value(state).map_err(|e| e.to_string())?;
+    redact::value(&mut payload);
+    let payload = payload.to_string();
+    let file = StateFile {
+        version: FORMAT_VERSION,
+        checksum: fnv1a64_hex(payload.as_bytes()),
+        payload,
+    };
+    let bytes = serde_json::to_vec(&file).map_err(|e| e.to_string())?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("no se pudo crear {}: {e}", dir.display()))?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    {
+        let mut f = fs::File::create(&tmp).map_err(|e| format!("no se pudo crear {}: {e}", tmp.display()))?;
+        f.write_all(&bytes).map_err(|e| e.to_string())?;
+        f.sync_all().map_err(|e| e.to_string())?;
+    }
+    // rename es atómico en el mismo filesystem: o queda el archivo viejo o el nuevo
+    fs::rename(&tmp, path).map_err(|e| format!("no se pudo reemplazar {}: {e}", path.display()))
+}
+
+pub fn load(path: &Path) -> Result<PersistedState, String> {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    let file: StateFile = serde_json::from_slice(&bytes).map_err(|e| format!("formato inválido: {e}"))?;
+    if file.version != FORMAT_VERSION {
+        return Err(format!("versión {} no soportada", file.version));
+    }
+    if fnv1a64_hex(file.payload.as_bytes()) != file.checksum {
+        return Err("checksum no coincide".to_string());
+    }
+    let state: PersistedState =
+        serde_json::from_str(&file.payload).map_err(|e| format!("payload inválido: {e}"))?;
+
+    // lo guardado tiene que seguir pasando la validación actual
+    for (name, layout) in [("current_layout", &state.current_layout), ("last_good_layout", &state.last_good_layout)] {
+        validate::validate_layout(layout).map_err(|v| format!("{name}: {}", validate::describe(&v)))?;
+    }
+    Ok(state)
+}
+
+// Archivo corrupto: se aparta (para diagnóstico) y se sigue con el layout incorporado
+pub fn quarantine(path: &Path) {
+    let bad = path.with_extension("json.corrupt");
+    if let Err(e) = fs::rename(path, &bad) {
+        eprintln!("[PERSIST] no se pudo apartar {}: {e}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{column, layout, text};
+
+    #[test]
+    fn card_data_never_reaches_disk() {
+        let dir = std::env::temp_dir().join(format!("persist-test-redact-{}", std::process::id()));
+        let path = state_file(&dir);
+        let shown = layout(column().child(text("msr_result", "leído ;4111111111111111=25121010000000000000?")));
+        let state = PersistedState {
+            current_layout: shown.clone(),
+            last_good_layout: shown,
+            style: None,
+            screen_id: None,
+            saved_at_millis: 1_712_345_678_901,
+        };
+        save(&path, &state).unwrap();
+        let raw = fs::read_to_string(&path).unwrap();
+        assert!(!raw.contains("4111111111111111") && !raw.contains("2512101"), "{raw}");
+        assert!(raw.contains("411111******1111=***"));
+        // lo enmascarado sigue siendo un estado válido y conserva el timestamp
+        assert_eq!(load(&path).unwrap().saved_at_millis, 1_712_345_678_901);
+    }
+}
@@ -0,0 +1,481 @@
+// Autorizador de pagos. El flujo de cobro (lib.rs) sólo conoce PaymentProcessor:
+//
+//  - mock: contesta acá mismo con reglas fijas (ver MockProcessor::decide) tras processor.mock_latency_ms
+//  - http: POST {processor.http_url}/authorize (y /capture, /void, /refund) a un stand-in local
+//  - broker: request/reply "payment.*" con el backend por el DEALER (ver upstream.rs)
+//
+// Un cobro es authorize + capture (ver sale). void anula una autorización, capturada o no;
+// refund devuelve todo o parte de lo capturado. De la tarjeta sólo viaja la vista enmascarada.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::checksum::fnv1a64_hex;
+use crate::config::{AppConfig, ProcessorConfig, ProcessorKind};
+use crate::track::MaskedCard;
+use crate::upstream::{RequestError, UiRequest, UpstreamHandle};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthRequest {
+    pub reference: String, // para cruzar con el log del autorizador
+    pub device_id: String,
+    pub amount_cents: u64,
+    pub card: MaskedCard,
+}
+
+// Lo que contesta authorize: aprobado (con transaction_id) o rechazado con motivo
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Authorization {
+    pub approved: bool,
+    #[serde(default)]
+    pub transaction_id: Option<String>,
+    #[serde(default)]
+    pub auth_code: Option<String>,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+impl Authorization {
+    fn declined(reason: impl Into<String>) -> Self {
+        Self { approved: false, reason: Some(reason.into()), ..Default::default() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProcessorError {
+    Timeout,
+    Unavailable(String), // no se llegó al autorizador
+    Rejected(String),    // llegó, pero la operación no corresponde (transacción desconocida, ya anulada, ...)
+    Protocol(String),    // contestó algo que no se entiende
+}
+
+impl fmt::Display for ProcessorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProcessorError::Timeout => write!(f, "sin respuesta del autorizador"),
+            ProcessorError::Unavailable(e) => write!(f, "autorizador no disponible: {e}"),
+            ProcessorError::Rejected(e) => write!(f, "operación rechazada: {e}"),
+            ProcessorError::Protocol(e) => write!(f, "respuesta inválida del autorizador: {e}"),
+        }
+    }
+}
+
+pub type ProcessorFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, ProcessorError>> + Send + 'a>>;
+
+pub trait PaymentProcessor: Send + Sync {
+    fn kind(&self) -> &'static str;
+    fn authorize(&self, request: AuthRequest) -> ProcessorFuture<'_, Authorization>;
+    fn capture(&self, transaction_id: String, amount_cents: u64) -> ProcessorFuture<'_, ()>;
+    fn void(&self, transaction_id: String) -> ProcessorFuture<'_, ()>;
+    fn refund(&self, transaction_id: String, amount_cents: u64) -> ProcessorFuture<'_, ()>;
+}
+
+// authorize + capture. Si la captura falla se anula la autorización y el cobro queda rechazado.
+pub async fn sale(processor: &dyn PaymentProcessor, request: AuthRequest) -> Result<Authorization, ProcessorError> {
+    let amount_cents = request.amount_cents;
+    let auth = processor.authorize(request).await?;
+    if !auth.approved {
+        return Ok(auth);
+    }
+    let Some(txn) = auth.transaction_id.clone().filter(|t| !t.is_empty()) else {
+        return Err(ProcessorError::Protocol("aprobado sin transaction_id".to_string()));
+    };
+    if let Err(e) = processor.capture(txn.clone(), amount_cents).await {
+        eprintln!("[PAY] ❌ captura de {txn}: {e}");
+        if let Err(e) = processor.void(txn.clone()).await {
+            eprintln!("[PAY] ❌ no se pudo anular {txn}: {e}");
+        }
+        return Ok(Authorization::declined(format!("no se pudo capturar: {e}")));
+    }
+    Ok(auth)
+}
+
+// --------------------- mock ---------------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TxnStatus {
+    Authorized,
+    Captured,
+    Voided,
+}
+
+impl TxnStatus {
+    fn label(self) -> &'static str {
+        match self {
+            TxnStatus::Authorized => "autorizada",
+            TxnStatus::Captured => "capturada",
+            TxnStatus::Voided => "anulada",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MockTxn {
+    amount_cents: u64,
+    status: TxnStatus,
+    refunded_cents: u64,
+}
+
+pub struct MockProcessor {
+    latency: Duration,
+    decline_over_cents: u64,
+    decline_last4: Vec<String>,
+    seq: AtomicU64,
+    txns: Mutex<HashMap<String, MockTxn>>,
+}
+
+impl MockProcessor {
+    pub fn new(cfg: &ProcessorConfig) -> Self {
+        Self {
+            latency: Duration::from_millis(cfg.mock_latency_ms),
+            decline_over_cents: cfg.mock_decline_over_cents,
+            decline_last4: cfg.mock_decline_last4.clone(),
+            seq: AtomicU64::new(1),
+            txns: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Reglas, en orden (mismo monto y tarjeta → misma respuesta):
+    //   centavos .99            → sin conexión (para probar errores / timeouts)
+    //   monto > decline_over    → excede el límite
+    //   últimos 4 en la lista   → tarjeta rechazada
+    //   centavos .51            → fondos insuficientes
+    //   lo demás                → aprobado
+    fn decide(&self, request: &AuthRequest) -> Result<Authorization, ProcessorError> {
+        let cents = request.amount_cents;
+        let pan = request.card.pan.as_deref().unwrap_or_default();
+        if cents % 100 == 99 {
+            return Err(ProcessorError::Unavailable("sin conexión con el emisor (simulado)".to_string()));
+        }
+        if cents > self.decline_over_cents {
+            return Ok(Authorization::declined("monto excede el límite"));
+        }
+        if self.decline_last4.iter().any(|last4| pan.ends_with(last4.as_str())) {
+            return Ok(Authorization::declined("tarjeta rechazada por el emisor"));
+        }
+        if cents % 100 == 51 {
+            return Ok(Authorization::declined("fondos insuficientes"));
+        }
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        let txn = format!("mock-{seq:06}");
+        let auth_code = fnv1a64_hex(format!("{pan}|{cents}|{seq}").as_bytes())[..6].to_uppercase();
+        self.txns.lock().unwrap().insert(txn.clone(), MockTxn { amount_cents: cents, status: TxnStatus::Authorized, refunded_cents: 0 });
+        Ok(Authorization { approved: true, transaction_id: Some(txn), auth_code: Some(auth_code), reason: None })
+    }
+
+    fn update(&self, txn: &str, op: impl FnOnce(&mut MockTxn) -> Result<(), String>) -> Result<(), ProcessorError> {
+        let mut txns = self.txns.lock().unwrap();
+        let entry = txns.get_mut(txn).ok_or_else(|| ProcessorError::Rejected(format!("transacción desconocida: {txn}")))?;
+        op(entry).map_err(ProcessorError::Rejected)
+    }
+}
+
+impl PaymentProcessor for MockProcessor {
+    fn kind(&self) -> &'static str {
+        "mock"
+    }
+
+    fn authorize(&self, request: AuthRequest) -> ProcessorFuture<'_, Authorization> {
+        Box::pin(async move {
+            tokio::time::sleep(self.latency).await;
+            self.decide(&request)
+        })
+    }
+
+    fn capture(&self, transaction_id: String, amount_cents: u64) -> ProcessorFuture<'_, ()> {
+        Box::pin(async move {
+            self.update(&transaction_id, |t| match t.status {
+                TxnStatus::Authorized if amount_cents <= t.amount_cents => {
+                    t.status = TxnStatus::Captured;
+                    t.amount_cents = amount_cents;
+                    Ok(())
+                }
+                TxnStatus::Authorized => Err(format!("se autorizaron {} centavos", t.amount_cents)),
+                other => Err(format!("la transacción está {}", other.label())),
+            })
+        })
+    }
+
+    fn void(&self, transaction_id: String) -> ProcessorFuture<'_, ()> {
+        Box::pin(async move {
+            self.update(&transaction_id, |t| match t.status {
+                TxnStatus::Authorized | TxnStatus::Captured if t.refunded_cents == 0 => {
+                    t.status = TxnStatus::Voided;
+                    Ok(())
+                }
+                TxnStatus::Voided => Err("ya está anulada".to_string()),
+                _ => Err("tiene devoluciones; no se puede anular".to_string()),
+            })
+        })
+    }
+
+    fn refund(&self, transaction_id: String, amount_cents: u64) -> ProcessorFuture<'_, ()> {
+        Box::pin(async move {
+            self.update(&transaction_id, |t| {
+                if t.status != TxnStatus::Captured {
+                    return Err(format!("sólo se devuelve lo capturado (está {})", t.status.label()));
+                }
+                if amount_cents == 0 || t.refunded_cents + amount_cents > t.amount_cents {
+                    return Err(format!("quedan {} centavos por devolver", t.amount_cents - t.refunded_cents));
+                }
+                t.refunded_cents += amount_cents;
+                Ok(())
+            })
+        })
+    }
+}
+
+// --------------------- http ---------------------
+// 2xx con JSON; 4xx = Rejected (con el "error" del cuerpo si viene), lo demás = Unavailable
+pub struct HttpProcessor {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl HttpProcessor {
+    pub fn new(base_url: &str, timeout: Duration) -> Result<Self, String> {
+        let client = reqwest::Client::builder().timeout(timeout).build().map_err(|e| e.to_string())?;
+        Ok(Self { base_url: base_url.trim_end_matches('/').to_string(), client })
+    }
+
+    async fn post<T: DeserializeOwned>(&self, op: &str, body: Value) -> Result<T, ProcessorError> {
+        let url = format!("{}/{op}", self.base_url);
+        let resp = self.client.post(&url).json(&body).send().await.map_err(|e| {
+            if e.is_timeout() { ProcessorError::Timeout } else { ProcessorError::Unavailable(e.to_string()) }
+        })?;
+        let status = resp.status();
+        let text = resp.text().await.map_err(|e| ProcessorError::Unavailable(e.to_string()))?;
+        if status.is_client_error() {
+            let error = serde_json::from_str::<Value>(&text).ok().and_then(|v| v.get("error")?.as_str().map(String::from));
+            return Err(ProcessorError::Rejected(error.unwrap_or_else(|| format!("HTTP {status}"))));
+        }
+        if !status.is_success() {
+            return Err(ProcessorError::Unavailable(format!("HTTP {status}")));
+        }
+        serde_json::from_str(if text.trim().is_empty() { "null" } else { &text })
+            .map_err(|e| ProcessorError::Protocol(format!("{op}: {e}")))
+    }
+}
+
+impl PaymentProcessor for HttpProcessor {
+    fn kind(&self) -> &'static str {
+        "http"
+    }
+
+    fn authorize(&self, request: AuthRequest) -> ProcessorFuture<'_, Authorization> {
+        Box::pin(async move { self.post("authorize", json!(request)).await })
+    }
+
+    fn capture(&self, transaction_id: String, amount_cents: u64) -> ProcessorFuture<'_, ()> {
+        Box::pin(async move {
+            self.post::<Value>("capture", json!({ "transaction_id": transaction_id, "amount_cents": amount_cents })).await.map(|_| ())
+        })
+    }
+
+    fn void(&self, transaction_id: String) -> ProcessorFuture<'_, ()> {
+        Box::pin(async move { self.post::<Value>("void", json!({ "transaction_id": transaction_id })).await.map(|_| ()) })
+    }
+
+    fn refund(&self, transaction_id: String, amount_cents: u64) -> ProcessorFuture<'_, ()> {
+        Box::pin(async move {
+            self.post::<Value>("refund", json!({ "transaction_id": transaction_id, "amount_cents": amount_cents })).await.map(|_| ())
+        })
+    }
+}
+
+// --------------------- broker ---------------------
+// Mismo contrato que http, como métodos "payment.authorize", "payment.capture", ...
+pub struct BrokerProcessor {
+    upstream: UpstreamHandle,
+    device_id: String,
+    timeout: Duration,
+}
+
+impl BrokerProcessor {
+    pub fn new(upstream: UpstreamHandle, device_id: String, timeout: Duration) -> Self {
+        Self { upstream, device_id, timeout }
+    }
+
+    async fn call(&self, op: &str, params: Value) -> Result<Value, ProcessorError> {
+        let request = UiRequest::new(self.device_id.clone(), &format!("payment.{op}"), params, self.timeout);
+        self.upstream.request(request).await.map_err(ProcessorError::from)
+    }
+}
+
+// El "error" del backend es su respuesta (como un 4xx en http); lo demás es no llegar
+impl From<RequestError> for ProcessorError {
+    fn from(e: RequestError) -> Self {
+        match e {
+            RequestError::Remote(e) => ProcessorError::Rejected(e),
+            RequestError::Timeout(_) => ProcessorError::Timeout,
+            RequestError::Unavailable(e) => ProcessorError::Unavailable(e),
+        }
+    }
+}
+
+impl PaymentProcessor for BrokerProcessor {
+    fn kind(&self) -> &'static str {
+        "broker"
+    }
+
+    fn authorize(&self, request: AuthRequest) -> ProcessorFuture<'_, Authorization> {
+        Box::pin(async move {
+            let reply = self.call("authorize", json!(request)).await?;
+            serde_json::from_value(reply).map_err(|e| ProcessorError::Protocol(format!("authorize: {e}")))
+        })
+    }
+
+    fn capture(&self, transaction_id: String, amount_cents: u64) -> ProcessorFuture<'_, ()> {
+        Box::pin(async move {
+            self.call("capture", json!({ "transaction_id": transaction_id, "amount_cents": amount_cents })).await.map(|_| ())
+        })
+    }
+
+    fn void(&self, transaction_id: String) -> ProcessorFuture<'_, ()> {
+        Box::pin(async move { self.call("void", json!({ "transaction_id": transaction_id })).await.map(|_| ()) })
+    }
+
+    fn refund(&self, transaction_id: String, amount_cents: u64) -> ProcessorFuture<'_, ()> {
+        Box::pin(async move {
+            self.call("refund", json!({ "transaction_id": transaction_id, "amount_cents": amount_cents })).await.map(|_| ())
+        })
+    }
+}
+
+pub fn from_config(cfg: &AppConfig, upstream: &UpstreamHandle) -> Result<Arc<dyn PaymentProcessor>, String> {
+    let p = &cfg.processor;
+    Ok(match p.kind {
+        ProcessorKind::Mock => Arc::new(MockProcessor::new(p)),
+        ProcessorKind::Http => {
+            let url = p.http_url.as_deref().unwrap_or_default();
+            Arc::new(HttpProcessor::new(url, Duration::from_millis(p.http_timeout_ms))?)
+        }
+        ProcessorKind::Broker => Arc::new(BrokerProcessor::new(
+            upstream.clone(),
+            cfg.device_id(),
+            Duration::from_millis(cfg.upstream.request_timeout_ms),
+        )),
+    })
+}
+
+// Procesador instalado en setup (según processor.kind)
+#[derive(Clone, Default)]
+pub struct ProcessorHandle {
+    processor: Arc<Mutex<Option<Arc<dyn PaymentProcessor>>>>,
+}
+
+impl ProcessorHandle {
+    pub fn install(&self, processor: Arc<dyn PaymentProcessor>) {
+        eprintln!("[PAY] procesador {}", processor.kind());
+        *self.processor.lock().unwrap() = Some(processor);
+    }
+
+    pub fn get(&self) -> Result<Arc<dyn PaymentProcessor>, ProcessorError> {
+        self.processor.lock().unwrap().clone().ok_or_else(|| ProcessorError::Unavailable("sin procesador configurado".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock() -> MockProcessor {
+        MockProcessor::new(&ProcessorConfig { mock_latency_ms: 0, mock_decline_over_cents: 500_000, ..Default::default() })
+    }
+
+    fn request(amount_cents: u64, pan: &str) -> AuthRequest {
+        AuthRequest {
+            reference: "pos-1-pay-1".to_string(),
+            device_id: "pos-1".to_string(),
+            amount_cents,
+            card: MaskedCard { pan: Some(pan.to_string()), ..Default::default() },
+        }
+    }
+
+    const CARD: &str = "411111******1111";
+
+    #[test]
+    fn mock_rules_in_order() {
+        let m = mock();
+        assert!(matches!(m.decide(&request(1_099, CARD)), Err(ProcessorError::Unavailable(_))));
+        // .99 gana aunque además exceda el límite
+        assert!(matches!(m.decide(&request(900_099, CARD)), Err(ProcessorError::Unavailable(_))));
+        let reason = |r: Result<Authorization, ProcessorError>| r.unwrap().reason;
+        assert_eq!(reason(m.decide(&request(500_001, CARD))).as_deref(), Some("monto excede el límite"));
+        assert_eq!(reason(m.decide(&request(1_000, "411111******0002"))).as_deref(), Some("tarjeta rechazada por el emisor"));
+        assert_eq!(reason(m.decide(&request(1_051, CARD))).as_deref(), Some("fondos insuficientes"));
+
+        let ok = m.decide(&request(500_000, CARD)).unwrap();
+        assert!(ok.approved);
+        assert_eq!(ok.transaction_id.as_deref(), Some("mock-000001"));
+        assert_eq!(ok.auth_code.as_ref().map(String::len), Some(6));
+        assert_eq!(m.decide(&request(1_000, CARD)).unwrap().transaction_id.as_deref(), Some("mock-000002"));
+    }
+
+    #[tokio::test]
+    async fn mock_capture_void_refund() {
+        let m = mock();
+        let txn = m.authorize(request(10_000, CARD)).await.unwrap().transaction_id.unwrap();
+        assert!(matches!(m.capture(txn.clone(), 20_000).await, Err(ProcessorError::Rejected(_))));
+        m.capture(txn.clone(), 8_000).await.unwrap();
+        m.refund(txn.clone(), 3_000).await.unwrap();
+        assert!(matches!(m.refund(txn.clone(), 6_000).await, Err(ProcessorError::Rejected(_))));
+        assert_eq!(m.void(txn.clone()).await, Err(ProcessorError::Rejected("tiene devoluciones; no se puede anular".into())));
+        assert!(matches!(m.void("mock-999999".into()).await, Err(ProcessorError::Rejected(_))));
+    }
+
+    // Mock cuya captura siempre falla
+    struct NoCapture(MockProcessor);
+
+    impl PaymentProcessor for NoCapture {
+        fn kind(&self) -> &'static str {
+            "no-capture"
+        }
+        fn authorize(&self, request: AuthRequest) -> ProcessorFuture<'_, Authorization> {
+            self.0.authorize(request)
+        }
+        fn capture(&self, _: String, _: u64) -> ProcessorFuture<'_, ()> {
+            Box::pin(async { Err(ProcessorError::Timeout) })
+        }
+        fn void(&self, transaction_id: String) -> ProcessorFuture<'_, ()> {
+            self.0.void(transaction_id)
+        }
+        fn refund(&self, transaction_id: String, amount_cents: u64) -> ProcessorFuture<'_, ()> {
+            self.0.refund(transaction_id, amount_cents)
+        }
+    }
+
+    #[tokio::test]
+    async fn sale_captures_or_voids() {
+        let m = mock();
+        let auth = sale(&m, request(2_500, CARD)).await.unwrap();
+        assert!(auth.approved);
+        assert_eq!(m.txns.lock().unwrap()[auth.transaction_id.as_deref().unwrap()].status, TxnStatus::Captured);
+
+        let p = NoCapture(mock());
+        let auth = sale(&p, request(2_500, CARD)).await.unwrap();
+        assert!(!auth.approved);
+        assert_eq!(auth.reason.as_deref(), Some("no se pudo capturar: sin respuesta del autorizador"));
+        assert_eq!(p.0.txns.lock().unwrap()["mock-000001"].status, TxnStatus::Voided);
+
+        // rechazos y errores del autorizador no llegan a capturar
+        assert!(!sale(&m, request(2_551, CARD)).await.unwrap().approved);
+        assert!(matches!(sale(&m, request(2_599, CARD)).await, Err(ProcessorError::Unavailable(_))));
+    }
+
+    #[tokio::test]
+    async fn broker_errors_keep_their_meaning() {
+        assert_eq!(ProcessorError::from(RequestError::Remote("txn desconocida".into())), ProcessorError::Rejected("txn desconocida".into()));
+        assert_eq!(ProcessorError::from(RequestError::Timeout("payment.void: sin respuesta".into())), ProcessorError::Timeout);
+        let off = BrokerProcessor::new(UpstreamHandle::default(), "pos-1".into(), Duration::from_millis(100));
+        assert!(matches!(off.void("t1".into()).await, Err(ProcessorError::Unavailable(_))));
+    }
+}
@@ -0,0 +1,180 @@
+// Enmascarado de datos de tarjeta (estilo PCI) para todo lo que sale de la app:
+// logs, previews de frames, ACKs y state.json. Lo que se ve de un PAN son los
+// primeros 6 y los últimos 4; de un track sólo el PAN enmascarado:
+//
+//   %B4111111111111111^DOE/JOHN^2512101000000000000?  →  %B411111******1111^***?
+//   4111111111111111=25121010000000000000            →  411111******1111=***
+//
+// Un PAN suelto se reconoce por largo (13-19), primer dígito (2-6) y Luhn, así que
+// timestamps en millis y msg_ids no se tocan. Pasarlo dos veces no cambia nada.
+
+use serde_json::Value;
+
+use crate::track::mask_pan;
+
+// Claves cuyo valor no se muestra nunca, sea lo que sea
+const SENSITIVE_KEYS: [&str; 6] = ["cvv", "cvv2", "cvc", "pin", "pin_block", "pinblock"];
+
+// Hasta dónde puede llegar el resto de un track 1 sin su '?' (79 caracteres como máximo)
+const TRACK1_TAIL_MAX: usize = 80;
+
+fn luhn_ok(digits: &str) -> bool {
+    let sum: u32 = digits
+        .bytes()
+        .rev()
+        .enumerate()
+        .map(|(i, b)| {
+            let d = u32::from(b - b'0');
+            if i % 2 == 1 { if d * 2 > 9 { d * 2 - 9 } else { d * 2 } } else { d }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+fn looks_like_pan(run: &str) -> bool {
+    (13..=19).contains(&run.len()) && matches!(run.as_bytes()[0], b'2'..=b'6') && luhn_ok(run)
+}
+
+// Un PAN empieza después de algo que no es alfanumérico (o de la 'B' del formato del track 1)
+fn at_boundary(b: &[u8], i: usize) -> bool {
+    match i.checked_sub(1).map(|p| b[p]) {
+        None => true,
+        Some(b'B') => i < 2 || !b[i - 2].is_ascii_alphanumeric(),
+        Some(prev) => !prev.is_ascii_alphanumeric(),
+    }
+}
+
+// Fin del resto del track (lo que va después de '^' o '=' del PAN)
+fn track_tail_end(s: &str, from: usize, sep: u8) -> usize {
+    let b = s.as_bytes();
+    if sep == b'=' {
+        return from + b[from..].iter().take_while(|c| matches!(c, b'0'..=b'9' | b'=' | b':' | b'<' | b'>')).count();
+    }
+    let limit = (from + TRACK1_TAIL_MAX).min(b.len());
+    let mut end = from + b[from..limit].iter().take_while(|c| !matches!(c, b'?' | b'"' | b'\\' | b'\n')).count();
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    end
+}
+
+pub fn text(s: &str) -> String {
+    let b = s.as_bytes();
+    let mut out = String::with_capacity(s.len());
+    let mut copied = 0;
+    let mut i = 0;
+    while i < b.len() {
+        if !b[i].is_ascii_digit() || !at_boundary(b, i) {
+            i += 1;
+            continue;
+        }
+        let end = i + b[i..].iter().take_while(|c| c.is_ascii_digit()).count();
+        let run = &s[i..end];
+        match b.get(end).copied() {
+            // PAN + separador de track: se va todo lo que sigue (vencimiento, service code, discrecional)
+            Some(sep @ (b'^' | b'=')) if (12..=19).contains(&run.len()) => {
+                let tail_end = track_tail_end(s, end + 1, sep);
+                out.push_str(&s[copied..i]);
+                out.push_str(&mask_pan(run));
+                out.push(sep as char);
+                out.push_str("***");
+                copied = tail_end;
+                i = tail_end;
+                continue;
+            }
+            next if looks_like_pan(run) && next.is_none_or(|c| !c.is_ascii_alphanumeric()) => {
+                out.push_str(&s[copied..i]);
+                out.push_str(&mask_pan(run));
+                copied = end;
+            }
+            _ => {}
+        }
+        i = end;
+    }
+    out.push_str(&s[copied..]);
+    out
+}
+
+// Todos los valores string del JSON (las claves quedan igual)
+pub fn value(v: &mut Value) {
+    match v {
+        Value::String(s) => *s = text(s),
+        Value::Array(items) => items.iter_mut().for_each(value),
+        Value::Object(map) => {
+            for (k, item) in map.iter_mut() {
+                if SENSITIVE_KEYS.contains(&k.to_ascii_lowercase().as_str()) && !item.is_null() {
+                    *item = Value::String("***".to_string());
+                } else {
+                    value(item);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const T1: &str = "%B4111111111111111^DOE/JOHN^25121010000000000000?";
+    const T2: &str = ";4111111111111111=25121010000000000000?";
+
+    #[test]
+    fn tracks_keep_only_the_masked_pan() {
+        assert_eq!(text(T1), "%B411111******1111^***?");
+        assert_eq!(text(T2), ";411111******1111=***?");
+        // con LRC (el ';' detrás del track 1 y el '8' del track 2 no revelan nada)
+        assert_eq!(text(&format!("{T1};{T2}8")), "%B411111******1111^***?;;411111******1111=***?8");
+        // sin centinelas, como lo dejan algunos lectores o un log
+        assert_eq!(text("4111111111111111=25121010000000000000"), "411111******1111=***");
+        assert_eq!(text("B4111111111111111^DOE/JOHN^2512101"), "B411111******1111^***");
+    }
+
+    #[test]
+    fn loose_pans_need_luhn() {
+        assert_eq!(text("tarjeta 4111111111111111, ok"), "tarjeta 411111******1111, ok");
+        assert_eq!(text("pan=5555555555554444"), "pan=555555******4444");
+        // no pasa Luhn / no empieza en 2-6 / pegado a letras: queda igual
+        for s in ["4111111111111112", "1712345678901", "id a4111111111111111", "4111111111111111x"] {
+            assert_eq!(text(s), s);
+        }
+    }
+
+    #[test]
+    fn timestamps_and_ids_are_left_alone() {
+        let s = r#"{"at_millis":1712345678901,"msg_id":"m-20240405-0001","issued_at":"2024-04-05T12:00:00Z"}"#;
+        assert_eq!(text(s), s);
+    }
+
+    #[test]
+    fn masking_twice_changes_nothing() {
+        for s in [T1, T2, "tarjeta 4111111111111111", "x ;4111111111111111=2512? y"] {
+            let once = text(s);
+            assert_eq!(text(&once), once);
+        }
+    }
+
+    #[test]
+    fn json_values_and_sensitive_keys() {
+        let mut v = json!({
+            "pan": "4111111111111111",
+            "CVV": "123",
+            "pin": null,
+            "card": { "pin_block": 1234, "tracks": [T2, "sin datos"] },
+            "amount": 4111111111111111u64
+        });
+        value(&mut v);
+        assert_eq!(
+            v,
+            json!({
+                "pan": "411111******1111",
+                "CVV": "***",
+                "pin": null,
+                "card": { "pin_block": "***", "tracks": [";411111******1111=***?", "sin datos"] },
+                "amount": 4111111111111111u64
+            })
+        );
+    }
+}
@@ -0,0 +1,200 @@
+// Pantallas locales (inicio y cobro), armadas con el builder → serde, sin format!.
+// Las del broker llegan por ui.apply / ui.style.apply; éstas son las que pinta la app sola.
+
+use crate::builder::{button, column, customer_display, input_money, layout, scroll, spacer, text, NodeBuilder};
+use crate::layout::{Align, CustomerDisplay, UiLayout};
+use crate::payment::PaymentState;
+use crate::redact;
+
+fn welcome_display() -> CustomerDisplay {
+    customer_display("Bienvenido").size(24).align(Align::Center).use_logo(true).bg_color("#000000")
+}
+
+pub fn build_base_layout() -> UiLayout {
+    build_start_layout("")
+}
+
+// Inicio con un aviso debajo del botón (p.ej. "Tiempo agotado" al volver de un cobro por timeout)
+pub fn build_start_layout(notice: &str) -> UiLayout {
+    let mut root = column()
+        .background("#FFFFFF")
+        .padding(24)
+        .gap(12)
+        .child(text("txt_title", "Inicio").align(Align::Center).size(22).bold().color("#111827"))
+        .child(spacer("sp_start_1", 12))
+        .child(
+            button("btn_proceed", "Proceder al cobro")
+                .on_click("go_payment")
+                .align(Align::Center)
+                .tint("#2962FF")
+                .text_color("#FFFFFF")
+                .enabled(true),
+        );
+    if !notice.is_empty() {
+        root = root.child(text("txt_notice", redact::text(notice)).align(Align::Center).size(14).color("#B45309"));
+    }
+
+    layout(root).background("#FFFFFF").customer_display(welcome_display())
+}
+
+// Una pantalla por estado del cobro (ver payment.rs); `message` va al área de resultado
+pub fn build_payment_layout(payment: &PaymentState, message: &str) -> UiLayout {
+    let small = |id: &str, txt: &str| text(id, txt).align(Align::Start).size(12);
+    let action = |id: &str, label: &str, event: &str, tint: &str, icon: &str| {
+        button(id, label).on_click(event).align(Align::Center).tint(tint).text_color("#FFFFFF").icon(icon)
+    };
+    let title = match payment.amount() {
+        Some(amount) => format!("Cobro: $ {amount}"),
+        None => "Cobro".to_string(),
+    };
+
+    let mut root = column().background("#FFFFFF").padding(24).gap(8);
+    // mientras se autoriza no se puede salir (la máquina lo rechazaría igual)
+    if !matches!(payment, PaymentState::Authorizing { .. }) {
+        root = root.child(
+            button("btn_back", "Regresar")
+                .on_click("nav_back")
+                .align(Align::Start)
+                .tint("#111827")
+                .text_color("#FFFFFF")
+                .icon("back"),
+        );
+    }
+    root = root
+        .child(spacer("sp_pay_2", 8))
+        .child(text("txt_pay_title", title).align(Align::Center).size(18).bold().color("#111827"));
+    root = match payment {
+        PaymentState::AmountEntry { .. } => root
+            .child(input_money("pay_amount").hint("Monto a cobrar"))
+            .child(action("btn_read_cancel", "Leer banda magnética", "btn_read_msr", "#D97706", "info")),
+        PaymentState::AwaitingCard { .. } => {
+            root.child(action("btn_read_cancel", "Cancelar lectura", "btn_cancel_msr", "#DC2626", "info"))
+        }
+        PaymentState::Approved { .. } => {
+            root.child(action("btn_print", "Imprimir comprobante", "print_from_button", "#2563EB", "print"))
+        }
+        PaymentState::Declined { .. } => {
+            root.child(action("btn_read_cancel", "Reintentar lectura", "btn_read_msr", "#D97706", "info"))
+        }
+        PaymentState::Idle | PaymentState::Authorizing { .. } | PaymentState::Receipt { .. } => root,
+    };
+    let root = root
+        .child(spacer("sp_pay_1", 12))
+        .child(text("txt_result_title", "Resultado de lectura").align(Align::Start).size(14).bold())
+        .child(small("msr_name", "Nombre: {{msr.cardholderName}}"))
+        .child(small("msr_pan", "Tarjeta: {{msr.pan}}  Vence: {{msr.expiry}}"))
+        .child(small("msr_t1", "Track 1: {{msr.track1}}"))
+        .child(small("msr_t2", "Track 2: {{msr.track2}}"))
+        .child(small("msr_t3", "Track 3: {{msr.track3}}"))
+        // serde se encarga del escape; los datos de tarjeta no llegan a la pantalla
+        .child(scroll("msr_result", redact::text(message)).weight(1.0).padding(12));
+
+    layout(root).background("#FFFFFF").customer_display(welcome_display())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payment::Amount;
+    use crate::track::MaskedCard;
+    use crate::validate;
+
+    // Tal cual lo armaba el format! de antes del builder
+    const OLD_BASE_JSON: &str = r###"
+{
+  "background": "#FFFFFF",
+  "root": {
+    "type": "column",
+    "background": "#FFFFFF",
+    "padding": 24,
+    "gap": 12,
+    "children": [
+      { "type": "text", "id": "txt_title", "text": "Inicio", "align": "center", "size": 22, "bold": true, "color": "#111827" },
+      { "type": "spacer", "id": "sp_start_1", "height": 12 },
+      { "type": "button", "id": "btn_proceed", "text": "Proceder al cobro", "on_click": "go_payment", "align": "center", "tint": "#2962FF", "text_color": "#FFFFFF", "enabled": true }
+    ]
+  },
+  "customer_display": { "text": "Bienvenido", "size": 24, "align": "center", "use_logo": true, "bg_color": "#000000" }
+}
+"###;
+
+    fn all_states() -> Vec<PaymentState> {
+        let amount = Amount { cents: 1250 };
+        let card = MaskedCard { pan: Some("411111******1111".to_string()), ..Default::default() };
+        vec![
+            PaymentState::AmountEntry { amount: None },
+            PaymentState::AmountEntry { amount: Some(amount) },
+            PaymentState::AwaitingCard { amount },
+            PaymentState::Authorizing { amount, card: card.clone() },
+            PaymentState::Approved { amount, card, auth_code: "A1".into(), transaction_id: "mock-000001".into() },
+            PaymentState::Declined { amount, reason: "fondos insuficientes".into() },
+            PaymentState::Receipt { amount, auth_code: "A1".into() },
+        ]
+    }
+
+    fn ids(layout: &UiLayout) -> Vec<String> {
+        let crate::layout::UiNode::Column(col) = &layout.root else { panic!("la raíz es una columna") };
+        col.children.iter().filter_map(|c| c.id().map(String::from)).collect()
+    }
+
+    #[test]
+    fn base_layout_matches_old_json() {
+        let old: UiLayout = serde_json::from_str(OLD_BASE_JSON).unwrap();
+        let new = build_base_layout();
+        assert_eq!(new, old);
+        let as_json = |s: &str| serde_json::from_str::<serde_json::Value>(s).unwrap();
+        assert_eq!(as_json(&new.to_json()), as_json(OLD_BASE_JSON));
+        assert!(validate::validate_layout(&new).is_ok());
+    }
+
+    #[test]
+    fn every_payment_screen_validates() {
+        for state in all_states() {
+            let layout = build_payment_layout(&state, "mensaje");
+            assert_eq!(validate::validate_layout(&layout), Ok(()), "{}", state.name());
+            // y sobrevive la vuelta por JSON (lo que recibe el front)
+            let json: serde_json::Value = serde_json::from_str(&layout.to_json()).unwrap();
+            assert_eq!(validate::parse_layout_value(&json), Ok(layout), "{}", state.name());
+        }
+    }
+
+    #[test]
+    fn payment_screen_controls_follow_state() {
+        let states = all_states();
+        let has = |i: usize, id: &str| ids(&build_payment_layout(&states[i], "")).iter().any(|x| x == id);
+        assert!(has(0, "pay_amount") && has(0, "btn_read_cancel") && has(0, "btn_back"));
+        assert!(has(2, "btn_read_cancel") && !has(2, "pay_amount"));
+        assert!(!has(3, "btn_back"), "mientras autoriza no se puede salir");
+        assert!(has(4, "btn_print"));
+        assert!(has(5, "btn_read_cancel"));
+        assert!(!has(6, "btn_print") && has(6, "btn_back"));
+    }
+
+    #[test]
+    fn payment_message_is_not_broken_by_quotes_or_newlines() {
+        // lo que rompía el format! de antes: comillas, \n, llaves y backslashes
+        let message = "Error de lectura: \"raro\"\nlínea 2 {x} \\ fin";
+        let layout = build_payment_layout(&PaymentState::AwaitingCard { amount: Amount { cents: 100 } }, message);
+        let json: serde_json::Value = serde_json::from_str(&layout.to_json()).unwrap();
+        let scroll = json["root"]["children"].as_array().unwrap().iter().find(|c| c["id"] == "msr_result").unwrap();
+        assert_eq!(scroll["text"], message);
+    }
+
+    #[test]
+    fn payment_message_is_masked() {
+        let message = "Error de lectura: %B4111111111111111^DOE/JOHN^2512101?";
+        let layout = build_payment_layout(&PaymentState::AwaitingCard { amount: Amount { cents: 100 } }, message);
+        let json = layout.to_json();
+        assert!(json.contains("%B411111******1111^***?"), "{json}");
+        assert!(!json.contains("4111111111111111") && !json.contains("DOE/JOHN"));
+    }
+
+    #[test]
+    fn start_screen_shows_the_notice_only_when_there_is_one() {
+        assert_eq!(build_start_layout(""), build_base_layout());
+        let timed_out = build_start_layout("Tiempo agotado");
+        assert!(validate::validate_layout(&timed_out).is_ok());
+        assert!(ids(&timed_out).contains(&"txt_notice".to_string()));
+        assert!(timed_out.to_json().contains("Tiempo agotado"));
+    }
+}
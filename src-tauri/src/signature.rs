@@ -0,0 +1,279 @@
+// Verificación de firma de los mensajes del broker (HMAC-SHA256 o Ed25519).
+// Cualquiera que llegue al PUB puede pintar la pantalla de pago: con un keyring
+// configurado sólo se aplica lo que viene firmado con una llave conocida.
+//
+//   { "envelope": { "msg_id": "a1", ..., "sig": { "alg": "ed25519", "kid": "2024-04", "value": "<base64>" } }, "cmd": {...} }
+//
+// Lo firmado es el mensaje completo SIN el objeto sig, en JSON canónico
+// (claves ordenadas, sin espacios). `sig` puede ir en envelope o top-level.
+//
+// Keyring (broker.keys_file en la config = ruta a un JSON); varias llaves a la vez = rotación por kid.
+// Con al menos una llave la firma es obligatoria; "allow_unsigned": true lo apaga a propósito
+// (p.ej. mientras el emisor empieza a firmar):
+//   { "allow_unsigned": false,
+//     "keys": [ { "kid": "2024-04", "alg": "ed25519", "public_key": "<base64 32 bytes>" },
+//               { "kid": "legacy",  "alg": "hmac-sha256", "secret": "<base64>" } ] }
+
+use std::collections::HashMap;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::Sha256;
+
+#[derive(Debug, Clone)]
+enum VerifyKey {
+    Hmac(Vec<u8>),
+    Ed25519(VerifyingKey),
+}
+
+#[derive(Deserialize)]
+struct KeyEntry {
+    kid: String,
+    alg: String,
+    #[serde(default)]
+    secret: Option<String>,
+    #[serde(default)]
+    public_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct KeyringFile {
+    #[serde(default)]
+    require_signed: bool,
+    #[serde(default)]
+    allow_unsigned: bool,
+    #[serde(default)]
+    keys: Vec<KeyEntry>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Keyring {
+    keys: HashMap<String, VerifyKey>,
+    require_signed: bool,
+}
+
+impl Keyring {
+    // Sin keys_file no se exige firma (salvo require_signed). Si está configurado pero no
+    // se puede leer, se falla cerrado: se exige firma con un keyring vacío (se rechaza todo).
+    pub fn load(keys_file: Option<&str>, require_signed: bool) -> Self {
+        let Some(path) = keys_file else {
+            return Self { keys: HashMap::new(), require_signed };
+        };
+        match std::fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|txt| Self::from_json(&txt)) {
+            Ok(mut ring) => {
+                ring.require_signed |= require_signed;
+                eprintln!(
+                    "[ZMQ] keyring {path}: {} llave(s), firma {}",
+                    ring.keys.len(),
+                    if ring.require_signed { "obligatoria" } else { "opcional" }
+                );
+                ring
+            }
+            Err(e) => {
+                eprintln!("[ZMQ] ❌ keyring {path} inválido ({e}); se rechazarán todos los mensajes");
+                Self { keys: HashMap::new(), require_signed: true }
+            }
+        }
+    }
+
+    pub fn from_json(txt: &str) -> Result<Self, String> {
+        let file: KeyringFile = serde_json::from_str(txt).map_err(|e| e.to_string())?;
+        let mut keys = HashMap::new();
+        for entry in file.keys {
+            let key = match entry.alg.as_str() {
+                "hmac-sha256" => {
+                    let secret = entry.secret.ok_or_else(|| format!("{}: falta secret", entry.kid))?;
+                    VerifyKey::Hmac(STANDARD.decode(secret).map_err(|e| format!("{}: secret no es base64: {e}", entry.kid))?)
+                }
+                "ed25519" => {
+                    let public = entry.public_key.ok_or_else(|| format!("{}: falta public_key", entry.kid))?;
+                    let bytes: [u8; 32] = STANDARD
+                        .decode(public)
+                        .ok()
+                        .and_then(|b| b.try_into().ok())
+                        .ok_or_else(|| format!("{}: public_key debe ser base64 de 32 bytes", entry.kid))?;
+                    VerifyKey::Ed25519(
+                        VerifyingKey::from_bytes(&bytes).map_err(|e| format!("{}: public_key inválida: {e}", entry.kid))?,
+                    )
+                }
+                other => return Err(format!("{}: alg desconocido '{other}'", entry.kid)),
+            };
+            keys.insert(entry.kid, key);
+        }
+        // sin firma obligatoria un atacante sólo tendría que no mandar sig
+        let require_signed = file.require_signed || (!keys.is_empty() && !file.allow_unsigned);
+        if file.allow_unsigned && !keys.is_empty() && !require_signed {
+            eprintln!("[ZMQ] ⚠️ keyring con allow_unsigned: se aceptan mensajes sin firma");
+        }
+        Ok(Self { keys, require_signed })
+    }
+
+    // Ok(Some(kid)) firmado y válido; Ok(None) sin firma y no se exige
+    pub fn verify(&self, message: &Value) -> Result<Option<String>, String> {
+        let Some((sig, unsigned)) = split_signature(message) else {
+            return if self.require_signed { Err("mensaje sin firma".to_string()) } else { Ok(None) };
+        };
+        let field = |k: &str| sig.get(k).and_then(|v| v.as_str()).ok_or_else(|| format!("sig sin '{k}'"));
+        let (alg, kid, value) = (field("alg")?, field("kid")?, field("value")?);
+        let raw = STANDARD.decode(value).map_err(|_| "sig.value no es base64".to_string())?;
+        let key = self.keys.get(kid).ok_or_else(|| format!("kid desconocido '{kid}'"))?;
+        let signed = canonical_json(&unsigned);
+
+        let ok = match (key, alg) {
+            (VerifyKey::Hmac(secret), "hmac-sha256") => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(secret).map_err(|e| e.to_string())?;
+                mac.update(signed.as_bytes());
+                mac.verify_slice(&raw).is_ok() // comparación en tiempo constante
+            }
+            (VerifyKey::Ed25519(public), "ed25519") => Signature::from_slice(&raw)
+                .map(|s| public.verify(signed.as_bytes(), &s).is_ok())
+                .unwrap_or(false),
+            _ => return Err(format!("alg '{alg}' no corresponde a la llave '{kid}'")),
+        };
+        if ok { Ok(Some(kid.to_string())) } else { Err(format!("firma inválida (kid '{kid}')")) }
+    }
+}
+
+// Saca el objeto sig (envelope.sig o top-level sig) y devuelve el resto
+fn split_signature(message: &Value) -> Option<(Value, Value)> {
+    let mut unsigned = message.clone();
+    let from_env = unsigned
+        .get_mut("envelope")
+        .and_then(|e| e.as_object_mut())
+        .and_then(|e| e.remove("sig"));
+    let sig = from_env.or_else(|| unsigned.as_object_mut()?.remove("sig"))?;
+    Some((sig, unsigned))
+}
+
+// JSON con claves ordenadas y sin espacios (independiente de cómo lo serializó el emisor).
+// Lo que tiene que reproducir quien firma (ver los vectores en los tests):
+//   - claves ordenadas por bytes UTF-8, en todos los niveles; los arreglos conservan su orden
+//   - strings en UTF-8 tal cual: sólo se escapan '"', '\\' y los de control (\n, \u0001, ...)
+//   - enteros sin ".0"; floats con la representación más corta que vuelve al mismo valor
+//     (1.0 → "1.0", 0.1 → "0.1", 1e-7 → "1e-7")
+pub fn canonical_json(v: &Value) -> String {
+    match v {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            let body: Vec<String> = keys
+                .into_iter()
+                .map(|k| format!("{}:{}", Value::String(k.clone()), canonical_json(&map[k])))
+                .collect();
+            format!("{{{}}}", body.join(","))
+        }
+        Value::Array(items) => format!("[{}]", items.iter().map(canonical_json).collect::<Vec<_>>().join(",")),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+    use serde_json::json;
+
+    const HMAC_SECRET: &[u8] = b"secreto-de-prueba";
+
+    fn ed_key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    fn ring(extra: &str) -> Keyring {
+        let keys = json!([
+            { "kid": "h1", "alg": "hmac-sha256", "secret": STANDARD.encode(HMAC_SECRET) },
+            { "kid": "2024-04", "alg": "ed25519", "public_key": STANDARD.encode(ed_key(1).verifying_key().as_bytes()) },
+            { "kid": "2024-10", "alg": "ed25519", "public_key": STANDARD.encode(ed_key(2).verifying_key().as_bytes()) },
+        ]);
+        Keyring::from_json(&format!(r#"{{ "keys": {keys} {extra} }}"#)).unwrap()
+    }
+
+    fn message() -> Value {
+        json!({ "envelope": { "msg_id": "a1", "seq": 42 }, "cmd": { "name": "ui.apply", "args": { "monto": 12.5 } } })
+    }
+
+    fn with_sig(mut msg: Value, alg: &str, kid: &str, raw: &[u8]) -> Value {
+        msg["envelope"]["sig"] = json!({ "alg": alg, "kid": kid, "value": STANDARD.encode(raw) });
+        msg
+    }
+
+    fn hmac_signed(msg: Value) -> Value {
+        let mut mac = Hmac::<Sha256>::new_from_slice(HMAC_SECRET).unwrap();
+        mac.update(canonical_json(&msg).as_bytes());
+        with_sig(msg, "hmac-sha256", "h1", &mac.finalize().into_bytes())
+    }
+
+    fn ed_signed(msg: Value, seed: u8, kid: &str) -> Value {
+        let sig = ed_key(seed).sign(canonical_json(&msg).as_bytes());
+        with_sig(msg, "ed25519", kid, &sig.to_bytes())
+    }
+
+    fn tamper(mut msg: Value) -> Value {
+        msg["cmd"]["args"]["monto"] = json!(99999);
+        msg
+    }
+
+    #[test]
+    fn hmac_valid_and_tampered() {
+        let ring = ring("");
+        assert_eq!(ring.verify(&hmac_signed(message())), Ok(Some("h1".to_string())));
+        assert_eq!(ring.verify(&tamper(hmac_signed(message()))), Err("firma inválida (kid 'h1')".to_string()));
+    }
+
+    #[test]
+    fn ed25519_valid_tampered_and_rotated() {
+        let ring = ring("");
+        assert_eq!(ring.verify(&ed_signed(message(), 1, "2024-04")), Ok(Some("2024-04".to_string())));
+        assert_eq!(ring.verify(&ed_signed(message(), 2, "2024-10")), Ok(Some("2024-10".to_string())));
+        assert!(ring.verify(&tamper(ed_signed(message(), 1, "2024-04"))).is_err());
+        // firmado con la llave nueva pero anunciando el kid viejo
+        assert_eq!(ring.verify(&ed_signed(message(), 2, "2024-04")), Err("firma inválida (kid '2024-04')".to_string()));
+    }
+
+    #[test]
+    fn unknown_kid_and_alg_mismatch() {
+        let ring = ring("");
+        assert_eq!(ring.verify(&ed_signed(message(), 1, "2023-01")), Err("kid desconocido '2023-01'".to_string()));
+        let mismatch = with_sig(message(), "hmac-sha256", "2024-04", b"x");
+        assert_eq!(ring.verify(&mismatch), Err("alg 'hmac-sha256' no corresponde a la llave '2024-04'".to_string()));
+        let top_level = {
+            let mut msg = message();
+            msg["sig"] = json!({ "alg": "ed25519", "kid": "2024-04", "value": "no base64 !" });
+            msg
+        };
+        assert_eq!(ring.verify(&top_level), Err("sig.value no es base64".to_string()));
+    }
+
+    #[test]
+    fn keys_imply_signature_required() {
+        assert_eq!(ring("").verify(&message()), Err("mensaje sin firma".to_string()));
+        assert_eq!(ring(r#", "allow_unsigned": true"#).verify(&message()), Ok(None));
+        // require_signed gana sobre allow_unsigned
+        let both = ring(r#", "allow_unsigned": true, "require_signed": true"#);
+        assert!(both.verify(&message()).is_err());
+        // sin llaves (ni keys_file) no se exige nada, salvo que se pida
+        assert_eq!(Keyring::default().verify(&message()), Ok(None));
+        assert!(Keyring::load(None, true).verify(&message()).is_err());
+        assert!(Keyring::load(Some("/no/existe/keys.json"), false).verify(&message()).is_err());
+    }
+
+    #[test]
+    fn canonical_json_vectors() {
+        let cases = [
+            (r#"{"b":1,"a":{"d":[3,1,2],"c":null},"A":true}"#, r#"{"A":true,"a":{"c":null,"d":[3,1,2]},"b":1}"#),
+            (r#"{ "texto" : "Año ñandú € 😀", "é": "x", "z": "y" }"#, r#"{"texto":"Año ñandú € 😀","z":"y","é":"x"}"#),
+            (r#"{"esc":"comillas \" barra \\ salto \n ctrl \u0001 slash \/"}"#, r#"{"esc":"comillas \" barra \\ salto \n ctrl \u0001 slash /"}"#),
+            (r#"[1.0, 0.1, 12.50, 1e-7, 2.5E3, -0.0, 100, -7]"#, r#"[1.0,0.1,12.5,1e-7,2500.0,-0.0,100,-7]"#),
+            // un \uXXXX del emisor se firma ya decodificado
+            (r#"{"unicode":"\u00f1"}"#, r#"{"unicode":"ñ"}"#),
+        ];
+        for (input, expected) in cases {
+            let v: Value = serde_json::from_str(input).unwrap();
+            assert_eq!(canonical_json(&v), expected, "{input}");
+        }
+    }
+}
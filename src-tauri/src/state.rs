@@ -1,64 +1,524 @@
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::ack::AckQueue;
+use crate::broker::ListenerHandle;
+use crate::commands::CommandRegistry;
+use crate::config::AppConfig;
+use crate::envelope::MessageGate;
+use crate::history::{LayoutHistory, RevisionSummary};
+use crate::layout::{StyleDocument, UiLayout};
+use crate::msr::MsrHandle;
+use crate::payment::PaymentMachine;
+use crate::persist::{self, PersistedState};
+use crate::processor::ProcessorHandle;
+use crate::template;
+use crate::upstream::{UiRequest, UpstreamHandle};
+use crate::validate::{self, Violation};
+
+// Estado de la conexión SUB con el broker (se emite como `broker_status`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BrokerConnState {
+    Connecting, // socket creado, esperando handshake
+    Connected,  // handshake OK y llegando tráfico
+    Stalled,    // conectado pero sin frames / conexión caída y zmq reintentando
+    Down,       // sesión rota; el supervisor está en backoff
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BrokerStatus {
+    pub state: BrokerConnState,
+    pub endpoint: String,
+    pub since_millis: i64,
+    pub last_error: Option<String>,
+    pub retries: u32,
+}
 
 #[derive(Clone)]
 pub struct AppState {
+    // config tipada (setup la carga y valida antes de arrancar los workers)
+    pub config: Arc<Mutex<AppConfig>>,
     // layout actual que la ventana debe estar mostrando
-    pub current_layout: Arc<Mutex<String>>,
+    pub current_layout: Arc<Mutex<UiLayout>>,
     // último layout válido conocido, por si llega uno roto
-    pub last_good_layout: Arc<Mutex<String>>,
+    pub last_good_layout: Arc<Mutex<UiLayout>>,
+    // último style recibido (ui.style.apply) y pantalla mostrada, para ui.screen.show
+    pub style_doc: Arc<Mutex<Option<StyleDocument>>>,
+    pub current_screen: Arc<Mutex<Option<String>>>,
+    // de dónde salió el layout actual (msg_id del broker, "payment:<estado>", ...)
+    pub current_source: Arc<Mutex<String>>,
+    // layouts aplicados (el último es el actual), para ui.rollback
+    pub history: Arc<Mutex<LayoutHistory>>,
+    // dónde se persiste el estado de UI (None hasta que setup conoce el app data dir)
+    pub persist_path: Arc<Mutex<Option<PathBuf>>>,
+    // un save a la vez: lo llaman el hilo ZMQ, upstream, timers del cobro y comandos
+    save_lock: Arc<Mutex<()>>,
+    // valores para los {{placeholders}} del layout (msr.*, etc.)
+    pub data_context: Arc<Mutex<Value>>,
 
     // ===== estado adicional que en Android vive en Lua / variables globales =====
-    pub reading: Arc<Mutex<bool>>,
+    pub payment: Arc<Mutex<PaymentMachine>>, // flujo de cobro (ver payment.rs)
     pub endpoint_snapshot: Arc<Mutex<String>>,       // equivalente a endpointSnapshot
     pub ack_endpoint_snapshot: Arc<Mutex<String>>,   // equivalente a ackEndpointSnapshot
     pub ack_init_snapshot: Arc<Mutex<bool>>,         // equivalente a ackInitSnapshot
-    pub last_hb_millis: Arc<Mutex<i64>>,             // heartbeat
+    pub last_hb_millis: Arc<Mutex<i64>>,             // último heartbeat aceptado
+    pub last_msg_millis: Arc<Mutex<i64>>,            // último frame recibido del broker
+    pub broker_status: Arc<Mutex<BrokerStatus>>,
+    // msg_ids vistos y último seq por source (duplicados / fuera de orden)
+    pub gate: Arc<Mutex<MessageGate>>,
+    // ACKs pendientes de entregar (los consume ack::ack_worker)
+    pub acks: AckQueue,
+    // hilo del listener ZMQ (broker_start / broker_stop / broker_set_endpoint)
+    pub listener: ListenerHandle,
+    // handlers de cmd.name del broker
+    pub commands: CommandRegistry,
+    // eventos de UI y requests con respuesta hacia el backend (DEALER)
+    pub upstream: UpstreamHandle,
+    // lector de banda magnética (msr.reader)
+    pub msr: MsrHandle,
+    // autorizador de cobros (processor.kind)
+    pub processor: ProcessorHandle,
 }
 
 impl AppState {
-    pub fn new(initial_layout: &str) -> Self {
+    pub fn new(initial_layout: UiLayout) -> Self {
+        let mut history = LayoutHistory::default();
+        history.record(initial_layout.clone(), "startup", None, None);
         Self {
-            current_layout: Arc::new(Mutex::new(initial_layout.to_string())),
-            last_good_layout: Arc::new(Mutex::new(initial_layout.to_string())),
-            reading: Arc::new(Mutex::new(false)),
+            config: Arc::new(Mutex::new(AppConfig::default())),
+            current_layout: Arc::new(Mutex::new(initial_layout.clone())),
+            last_good_layout: Arc::new(Mutex::new(initial_layout)),
+            style_doc: Arc::new(Mutex::new(None)),
+            current_screen: Arc::new(Mutex::new(None)),
+            current_source: Arc::new(Mutex::new("startup".to_string())),
+            history: Arc::new(Mutex::new(history)),
+            persist_path: Arc::new(Mutex::new(None)),
+            save_lock: Arc::new(Mutex::new(())),
+            data_context: Arc::new(Mutex::new(Value::Object(Default::default()))),
+            payment: Arc::new(Mutex::new(PaymentMachine::default())),
             endpoint_snapshot: Arc::new(Mutex::new(String::new())),
             ack_endpoint_snapshot: Arc::new(Mutex::new(String::new())),
             ack_init_snapshot: Arc::new(Mutex::new(false)),
             last_hb_millis: Arc::new(Mutex::new(0)),
+            last_msg_millis: Arc::new(Mutex::new(0)),
+            broker_status: Arc::new(Mutex::new(BrokerStatus {
+                state: BrokerConnState::Down,
+                endpoint: String::new(),
+                since_millis: Utc::now().timestamp_millis(),
+                last_error: None,
+                retries: 0,
+            })),
+            gate: Arc::new(Mutex::new(MessageGate::default())),
+            acks: AckQueue::default(),
+            listener: ListenerHandle::default(),
+            commands: CommandRegistry::with_builtins(),
+            upstream: UpstreamHandle::default(),
+            msr: MsrHandle::default(),
+            processor: ProcessorHandle::default(),
+        }
+    }
+
+    pub fn config(&self) -> AppConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    // Valida y aplica cambios a la config; si tocan al broker, el listener reabre la sesión.
+    // (heartbeat y ACK leen la URL en cada envío; el timeout del heartbeat también, el del ACK requiere reiniciar)
+    pub fn update_config(&self, changes: Value) -> Result<AppConfig, Vec<String>> {
+        let mut current = self.config.lock().unwrap();
+        let updated = crate::config::apply_update(&current, changes)?;
+        let broker_changed = updated.broker != current.broker;
+        *current = updated.clone();
+        drop(current);
+        if broker_changed && self.listener.request_reload() {
+            eprintln!("[CONFIG] broker cambió → reiniciando la sesión ZMQ");
         }
+        Ok(updated)
     }
 
+    // config.update recibido por ZMQ: sólo los campos de config::BROKER_UPDATABLE
+    pub fn update_config_from_broker(&self, changes: Value) -> Result<AppConfig, Vec<String>> {
+        crate::config::check_broker_update(&changes)?;
+        self.update_config(changes)
+    }
+
+    // Request/reply con el backend por el DEALER (ver upstream.rs).
+    // timeout None = upstream.request_timeout_ms de la config.
+    pub async fn request(&self, method: &str, params: Value, timeout: Option<Duration>) -> Result<Value, String> {
+        let cfg = self.config();
+        let timeout = timeout.unwrap_or(Duration::from_millis(cfg.upstream.request_timeout_ms));
+        self.upstream
+            .request(UiRequest::new(cfg.device_id(), method, params, timeout))
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    // JSON tal cual lo consume el front: con los {{placeholders}} ya resueltos
     pub fn get_layout(&self) -> String {
-        self.current_layout.lock().unwrap().clone()
+        let layout = self.current_layout.lock().unwrap().clone();
+        let ctx = self.data_context.lock().unwrap().clone();
+        template::resolve_layout(&layout, &ctx).to_json()
+    }
+
+    pub fn get_data(&self) -> Value {
+        self.data_context.lock().unwrap().clone()
+    }
+
+    // Devuelve true si cambió algo que el layout actual muestra
+    // (el llamador tiene que re-emitir layout_update).
+    pub fn set_data(&self, path: &str, value: Value) -> bool {
+        let changed = template::set_path(&mut self.data_context.lock().unwrap(), path, value);
+        changed && template::layout_binds(&self.current_layout.lock().unwrap(), path)
+    }
+
+    // Sólo lo que pasa la validación completa llega a current y lastGood;
+    // si no, se devuelven las violaciones y el estado queda intacto.
+    // `source` queda en el historial: msg_id del broker, "ui:<evento>" o "payment:<estado>".
+    pub fn apply_layout_safely(&self, candidate: &serde_json::Value, source: &str) -> Result<(), Vec<Violation>> {
+        let layout = validate::parse_layout_value(candidate)?;
+        self.promote(layout, source);
+        Ok(())
     }
 
-    pub fn apply_layout_safely(&self, new_layout: &str) -> bool {
-        // Validación básica: ¿es JSON válido?
-        if serde_json::from_str::<serde_json::Value>(new_layout).is_err() {
-            return false;
+    pub fn apply_layout(&self, layout: UiLayout, source: &str) -> Result<(), Vec<Violation>> {
+        validate::validate_layout(&layout)?;
+        self.promote(layout, source);
+        Ok(())
+    }
+
+    // Aplica una pantalla del style y, si valida, lo deja cacheado para ui.screen.show
+    pub fn apply_style(&self, style: StyleDocument, screen_id: Option<&str>, source: &str) -> Result<(), Vec<Violation>> {
+        let layout = style_layout(&style, screen_id)?;
+        validate::validate_layout(&layout)?;
+        let shown = screen_id.map(str::to_string).or_else(|| style.screens.first().and_then(|s| s.id.clone()));
+        *self.style_doc.lock().unwrap() = Some(style);
+        *self.current_screen.lock().unwrap() = shown;
+        self.promote(layout, source);
+        Ok(())
+    }
+
+    // Cambia de pantalla sin que el broker reenvíe el style (ni el logo)
+    pub fn show_screen(&self, id: &str, source: &str) -> Result<(), Vec<Violation>> {
+        let style = self.style_doc.lock().unwrap().clone();
+        let Some(style) = style else {
+            return Err(vec![Violation::new("$", "no hay style cacheado (falta ui.style.apply)")]);
+        };
+        let layout = style_layout(&style, Some(id))?;
+        validate::validate_layout(&layout)?;
+        *self.current_screen.lock().unwrap() = Some(id.to_string());
+        self.promote(layout, source);
+        Ok(())
+    }
+
+    // Modifica el layout actual como JSON (patch / set por id). Si el resultado
+    // no valida no se toca nada: current y lastGood quedan como estaban.
+    pub fn update_layout(
+        &self,
+        source: &str,
+        mutate: impl FnOnce(&mut Value) -> Result<(), String>,
+    ) -> Result<(), Vec<Violation>> {
+        let mut current = self.current_layout.lock().unwrap();
+        let mut doc = serde_json::to_value(&*current).map_err(|e| vec![Violation::new("$", e.to_string())])?;
+        mutate(&mut doc).map_err(|e| vec![Violation::new("$", e)])?;
+        let layout = validate::parse_layout_value(&doc)?;
+        *current = layout.clone();
+        drop(current);
+        self.commit(layout, source);
+        Ok(())
+    }
+
+    fn promote(&self, layout: UiLayout, source: &str) {
+        *self.current_layout.lock().unwrap() = layout.clone();
+        self.commit(layout, source);
+    }
+
+    // current ya tiene `layout`: pasa a ser lastGood, entra al historial y se persiste.
+    // Las pantallas locales no entran al historial: ui.rollback deshace lo que mandó el broker.
+    fn commit(&self, layout: UiLayout, source: &str) {
+        *self.last_good_layout.lock().unwrap() = layout.clone();
+        *self.current_source.lock().unwrap() = source.to_string();
+        if !is_local_source(source) {
+            let screen = self.current_screen.lock().unwrap().clone();
+            let style = self.style_doc.lock().unwrap().clone();
+            self.history.lock().unwrap().record(layout, source, screen, style);
+            self.leave_payment();
         }
-        // si es válido, promovemos a current y lastGood
-        *self.current_layout.lock().unwrap() = new_layout.to_string();
-        *self.last_good_layout.lock().unwrap() = new_layout.to_string();
-        true
+        self.save();
+    }
+
+    // Vuelve a una revisión anterior (ya validada cuando se aplicó) y descarta las posteriores
+    pub fn rollback(&self, steps: Option<u64>, version: Option<u64>) -> Result<u64, String> {
+        let rev = self.history.lock().unwrap().rollback(steps, version)?;
+        *self.current_layout.lock().unwrap() = rev.layout.clone();
+        *self.last_good_layout.lock().unwrap() = rev.layout;
+        *self.current_screen.lock().unwrap() = rev.screen_id;
+        *self.style_doc.lock().unwrap() = rev.style;
+        *self.current_source.lock().unwrap() = rev.source;
+        self.leave_payment();
+        self.save();
+        Ok(rev.version)
+    }
+
+    // Un layout del broker tapó la pantalla de cobro: la máquina vuelve a idle (sus timers y
+    // una autorización en curso quedan viejos; lo aprobado tarde se anula en authorize)
+    fn leave_payment(&self) {
+        let left = {
+            let mut machine = self.payment.lock().unwrap();
+            let name = machine.state().name();
+            machine.reset().then_some(name)
+        };
+        let Some(name) = left else { return };
+        eprintln!("[PAY] {name} → idle: el broker reemplazó la pantalla de cobro");
+        if name == "awaiting_card" {
+            if let Ok(reader) = self.msr.get() {
+                reader.cancel();
+            }
+        }
+        self.set_data("msr", Value::Null);
+    }
+
+    // ¿Lo que está en pantalla es la del cobro? (si no, la máquina no debe repintar)
+    pub fn showing_payment(&self) -> bool {
+        self.current_source.lock().unwrap().starts_with("payment:")
+    }
+
+    pub fn list_history(&self) -> Vec<RevisionSummary> {
+        self.history.lock().unwrap().summaries()
     }
 
     pub fn restore_last_good(&self) {
         let last = self.last_good_layout.lock().unwrap().clone();
         *self.current_layout.lock().unwrap() = last;
+        self.save();
     }
 
-    pub fn set_reading(&self, r: bool) {
-        *self.reading.lock().unwrap() = r;
+    // --------------------- persistencia ---------------------
+    fn snapshot(&self) -> PersistedState {
+        PersistedState {
+            current_layout: self.current_layout.lock().unwrap().clone(),
+            last_good_layout: self.last_good_layout.lock().unwrap().clone(),
+            style: self.style_doc.lock().unwrap().clone(),
+            screen_id: self.current_screen.lock().unwrap().clone(),
+            saved_at_millis: Utc::now().timestamp_millis(),
+        }
     }
-    pub fn get_reading(&self) -> bool {
-        *self.reading.lock().unwrap()
+
+    // Las pantallas locales (ver is_local_source) no se guardan: al reiniciar la máquina
+    // de cobro arranca en idle y sus botones no corresponderían. Queda lo último del broker.
+    fn save(&self) {
+        let Some(path) = self.persist_path.lock().unwrap().clone() else { return };
+        let _guard = self.save_lock.lock().unwrap();
+        if is_local_source(&self.current_source.lock().unwrap()) {
+            return;
+        }
+        if let Err(e) = persist::save(&path, &self.snapshot()) {
+            eprintln!("[PERSIST] no se pudo guardar {}: {e}", path.display());
+        }
+    }
+
+    // Carga lo guardado (si es válido) y desde ahí persiste cada cambio.
+    // Devuelve true si se restauró algo.
+    pub fn enable_persistence(&self, path: PathBuf) -> bool {
+        let restored = match persist::load(&path) {
+            Ok(saved) => {
+                self.history
+                    .lock()
+                    .unwrap()
+                    .record(saved.last_good_layout.clone(), "restored", saved.screen_id.clone(), saved.style.clone());
+                *self.current_layout.lock().unwrap() = saved.current_layout;
+                *self.last_good_layout.lock().unwrap() = saved.last_good_layout;
+                *self.style_doc.lock().unwrap() = saved.style;
+                *self.current_screen.lock().unwrap() = saved.screen_id;
+                *self.current_source.lock().unwrap() = "restored".to_string();
+                eprintln!("[PERSIST] estado restaurado de {}", path.display());
+                true
+            }
+            Err(e) if path.exists() => {
+                eprintln!("[PERSIST] ❌ {} corrupto ({e}); se usa el layout incorporado", path.display());
+                persist::quarantine(&path);
+                false
+            }
+            Err(_) => false,
+        };
+        *self.persist_path.lock().unwrap() = Some(path);
+        restored
+    }
+
+    // ok=false deja last_hb_millis en el último heartbeat bueno
+    pub fn record_heartbeat(&self, ok: bool, at_millis: i64) {
+        *self.ack_init_snapshot.lock().unwrap() = ok;
+        if ok {
+            *self.last_hb_millis.lock().unwrap() = at_millis;
+        }
+    }
+
+    pub fn mark_message_received(&self) {
+        *self.last_msg_millis.lock().unwrap() = Utc::now().timestamp_millis();
+    }
+    pub fn get_last_message_millis(&self) -> i64 {
+        *self.last_msg_millis.lock().unwrap()
+    }
+
+    pub fn get_broker_status(&self) -> BrokerStatus {
+        self.broker_status.lock().unwrap().clone()
+    }
+
+    // Devuelve el status nuevo sólo si cambió de estado (para no inundar al front)
+    pub fn set_broker_state(
+        &self,
+        endpoint: &str,
+        conn: BrokerConnState,
+        error: Option<String>,
+        retries: u32,
+    ) -> Option<BrokerStatus> {
+        *self.endpoint_snapshot.lock().unwrap() = endpoint.to_string();
+        let mut st = self.broker_status.lock().unwrap();
+        if st.state == conn && st.endpoint == endpoint && st.last_error == error {
+            st.retries = retries;
+            return None;
+        }
+        *st = BrokerStatus {
+            state: conn,
+            endpoint: endpoint.to_string(),
+            since_millis: Utc::now().timestamp_millis(),
+            last_error: error,
+            retries,
+        };
+        Some(st.clone())
+    }
+}
+
+// Pantallas que arma la app sola ("ui:<evento>", "payment:<estado>"); el resto vino del broker
+pub fn is_local_source(source: &str) -> bool {
+    source.starts_with("ui:") || source.starts_with("payment:")
+}
+
+fn style_layout(style: &StyleDocument, screen_id: Option<&str>) -> Result<UiLayout, Vec<Violation>> {
+    style.to_layout(screen_id).ok_or_else(|| {
+        let msg = match screen_id {
+            Some(id) => format!("el style no tiene la pantalla '{id}' (hay: {:?})", style.screen_ids()),
+            None => "el style no tiene pantallas".to_string(),
+        };
+        vec![Violation::new("$.screens", msg)]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{column, layout, text};
+
+    fn state() -> AppState {
+        AppState::new(layout(column().child(text("t", "inicio"))))
+    }
+
+    #[test]
+    fn broker_state_dedups_same_error() {
+        let st = state();
+        let stalled = || Some("sin frames hace más de 30s".to_string());
+        assert!(st.set_broker_state("tcp://x:5556", BrokerConnState::Stalled, stalled(), 0).is_some());
+        // mismo estado y mismo error: no hay evento nuevo, sólo se actualiza retries
+        assert!(st.set_broker_state("tcp://x:5556", BrokerConnState::Stalled, stalled(), 3).is_none());
+        assert_eq!(st.get_broker_status().retries, 3);
+        assert!(st.set_broker_state("tcp://x:5556", BrokerConnState::Connected, None, 0).is_some());
+    }
+
+    fn temp_state_file(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("state-test-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        persist::state_file(&dir)
+    }
+
+    #[test]
+    fn concurrent_saves_leave_a_loadable_file() {
+        let path = temp_state_file("concurrent");
+        let st = state();
+        st.enable_persistence(path.clone());
+        let writers: Vec<_> = (0..8)
+            .map(|i| {
+                let st = st.clone();
+                std::thread::spawn(move || {
+                    for j in 0..20 {
+                        let l = layout(column().child(text("t", format!("w{i}-{j}"))));
+                        st.apply_layout(l, &format!("m{i}-{j}")).unwrap();
+                    }
+                })
+            })
+            .collect();
+        writers.into_iter().for_each(|w| w.join().unwrap());
+        let saved = persist::load(&path).expect("state.json intacto");
+        assert_eq!(saved.current_layout, *st.current_layout.lock().unwrap());
+        assert!(!path.with_extension("json.tmp").exists());
     }
 
-    pub fn set_status(&self, zmq: &str, ack: &str, ack_init: bool, last_hb: i64) {
-        *self.endpoint_snapshot.lock().unwrap() = zmq.to_string();
-        *self.ack_endpoint_snapshot.lock().unwrap() = ack.to_string();
-        *self.ack_init_snapshot.lock().unwrap() = ack_init;
-        *self.last_hb_millis.lock().unwrap() = last_hb;
+    #[test]
+    fn local_screens_are_not_persisted() {
+        let path = temp_state_file("local");
+        let st = state();
+        st.enable_persistence(path.clone());
+        let broker = layout(column().child(text("t", "del broker")));
+        st.apply_layout(broker.clone(), "msg-1").unwrap();
+        st.apply_layout(layout(column().child(text("t", "cobro"))), "payment:awaiting_card").unwrap();
+        st.restore_last_good();
+        assert_eq!(persist::load(&path).unwrap().current_layout, broker);
+    }
+
+    fn style(screens: &[&str]) -> StyleDocument {
+        let screens: Vec<Value> = screens
+            .iter()
+            .map(|id| serde_json::json!({"id": id, "children": [{"type": "text", "text": id}]}))
+            .collect();
+        serde_json::from_value(serde_json::json!({ "screens": screens })).unwrap()
+    }
+
+    #[test]
+    fn local_screens_stay_out_of_history() {
+        let st = state();
+        st.apply_layout(layout(column().child(text("t", "uno"))), "msg-1").unwrap();
+        st.apply_layout(layout(column().child(text("t", "cobro"))), "payment:amount_entry").unwrap();
+        st.apply_layout(layout(column().child(text("t", "evento"))), "ui:btn_ok").unwrap();
+        let sources: Vec<String> = st.list_history().into_iter().map(|r| r.source).collect();
+        assert_eq!(sources, ["msg-1", "startup"]);
+    }
+
+    #[test]
+    fn rollback_restores_the_style_of_that_revision() {
+        let st = state();
+        st.apply_style(style(&["a1", "a2"]), None, "msg-a").unwrap();
+        st.apply_style(style(&["b1"]), None, "msg-b").unwrap();
+        st.apply_layout(layout(column().child(text("t", "cobro"))), "payment:awaiting_card").unwrap();
+
+        st.rollback(Some(1), None).unwrap();
+        assert_eq!(st.current_screen.lock().unwrap().as_deref(), Some("a1"));
+        assert_eq!(*st.style_doc.lock().unwrap(), Some(style(&["a1", "a2"])));
+        // la pantalla hermana sale del style restaurado, no del de msg-b
+        st.show_screen("a2", "msg-c").unwrap();
+    }
+
+    #[test]
+    fn broker_layout_resets_the_payment_machine() {
+        use crate::payment::PaymentEvent;
+        let st = state();
+        let epoch = {
+            let mut machine = st.payment.lock().unwrap();
+            machine.apply(PaymentEvent::Start).unwrap();
+            machine.snapshot().epoch
+        };
+        st.apply_layout(layout(column().child(text("t", "cobro"))), "payment:amount_entry").unwrap();
+        assert!(st.showing_payment());
+        assert_eq!(st.payment.lock().unwrap().state().name(), "amount_entry");
+
+        st.apply_layout(layout(column().child(text("t", "promo"))), "msg-9").unwrap();
+        assert!(!st.showing_payment());
+        let mut machine = st.payment.lock().unwrap();
+        assert_eq!(machine.state().name(), "idle");
+        // el timer de amount_entry ya no aplica
+        assert_eq!(machine.apply(PaymentEvent::Timeout { epoch }), Ok(false));
     }
 }
@@ -0,0 +1,204 @@
+// Sustitución de {{ruta.al.valor}} en los textos del layout contra el
+// data-context de AppState. El layout guardado conserva las plantillas;
+// sólo lo que se emite al front va resuelto.
+
+use serde_json::Value;
+
+use crate::layout::{UiLayout, UiNode};
+
+// Busca "msr.track1" / "items.0.name" dentro del contexto
+pub fn lookup<'a>(ctx: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').filter(|p| !p.is_empty()).try_fold(ctx, |cur, key| match cur {
+        Value::Object(map) => map.get(key),
+        Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => None,
+    })
+}
+
+fn display(v: Option<&Value>) -> String {
+    match v {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+// Rutas {{...}} presentes en un texto, en orden
+pub fn placeholders(text: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else { break };
+        out.push(rest[start + 2..start + 2 + len].trim());
+        rest = &rest[start + 2 + len + 2..];
+    }
+    out
+}
+
+pub fn render(text: &str, ctx: &Value) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else { break };
+        out.push_str(&rest[..start]);
+        out.push_str(&display(lookup(ctx, rest[start + 2..start + 2 + len].trim())));
+        rest = &rest[start + 2 + len + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+// --------------------- recorrido de textos ---------------------
+fn for_each_text(node: &UiNode, f: &mut impl FnMut(&str)) {
+    match node {
+        UiNode::Text(t) => {
+            f(&t.text);
+            if let Some(tpl) = &t.text_template {
+                f(tpl);
+            }
+        }
+        UiNode::Button(b) => f(&b.text),
+        UiNode::Scroll(s) => f(&s.text),
+        UiNode::Column(c) => c.children.iter().for_each(|child| for_each_text(child, f)),
+        _ => {}
+    }
+}
+
+fn render_node(node: &mut UiNode, ctx: &Value) {
+    match node {
+        UiNode::Text(t) => {
+            // text_template (si viene) manda sobre text
+            let source = t.text_template.as_deref().unwrap_or(&t.text);
+            t.text = render(source, ctx);
+        }
+        UiNode::Button(b) => b.text = render(&b.text, ctx),
+        UiNode::Scroll(s) => s.text = render(&s.text, ctx),
+        UiNode::Column(c) => c.children.iter_mut().for_each(|child| render_node(child, ctx)),
+        _ => {}
+    }
+}
+
+pub fn resolve_layout(layout: &UiLayout, ctx: &Value) -> UiLayout {
+    let mut out = layout.clone();
+    render_node(&mut out.root, ctx);
+    out
+}
+
+// ¿Algún placeholder del layout depende de `path`? (igual, padre o hijo)
+pub fn layout_binds(layout: &UiLayout, path: &str) -> bool {
+    let related = |p: &str| {
+        p == path
+            || p.strip_prefix(path).is_some_and(|r| r.starts_with('.'))
+            || path.strip_prefix(p).is_some_and(|r| r.starts_with('.'))
+    };
+    let mut hit = false;
+    for_each_text(&layout.root, &mut |txt| {
+        hit = hit || placeholders(txt).into_iter().any(related);
+    });
+    hit
+}
+
+// Escribe `value` en `path` creando los objetos intermedios.
+// Devuelve true si el valor cambió.
+pub fn set_path(ctx: &mut Value, path: &str, value: Value) -> bool {
+    let keys: Vec<&str> = path.split('.').filter(|p| !p.is_empty()).collect();
+    let Some((last, parents)) = keys.split_last() else { return false };
+
+    let mut cur = ctx;
+    for key in parents {
+        if !cur.is_object() {
+            *cur = Value::Object(Default::default());
+        }
+        cur = cur
+            .as_object_mut()
+            .expect("recién convertido a objeto")
+            .entry(key.to_string())
+            .or_insert_with(|| Value::Object(Default::default()));
+    }
+    if !cur.is_object() {
+        *cur = Value::Object(Default::default());
+    }
+    let map = cur.as_object_mut().expect("recién convertido a objeto");
+    if map.get(*last) == Some(&value) {
+        return false;
+    }
+    map.insert(last.to_string(), value);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{button, column, layout, scroll, text};
+    use crate::commands::{CommandContext, CommandOutcome};
+    use crate::state::AppState;
+    use serde_json::json;
+
+    #[test]
+    fn lookup_nested_and_array_index() {
+        let ctx = json!({ "msr": { "name": "JOHN DOE" }, "items": [{ "name": "café" }, { "name": "pan", "qty": 2 }] });
+        assert_eq!(lookup(&ctx, "msr.name"), Some(&json!("JOHN DOE")));
+        assert_eq!(lookup(&ctx, "items.1.qty"), Some(&json!(2)));
+        assert_eq!(lookup(&ctx, "items.9.name"), None);
+        assert_eq!(lookup(&ctx, "items.x"), None);
+        assert_eq!(lookup(&ctx, "msr.name.first"), None);
+        assert_eq!(render("{{ items.0.name }} x{{items.1.qty}} para {{msr.name}}", &ctx), "café x2 para JOHN DOE");
+    }
+
+    #[test]
+    fn missing_keys_render_empty() {
+        let ctx = json!({ "a": null });
+        assert_eq!(render("[{{a}}][{{b.c}}]", &ctx), "[][]");
+        // sin cierre se deja tal cual
+        assert_eq!(render("hola {{sin cierre", &ctx), "hola {{sin cierre");
+        assert_eq!(placeholders("{{a}} y {{ b.c }} {{x"), ["a", "b.c"]);
+    }
+
+    #[test]
+    fn resolve_keeps_the_templates() {
+        let l = layout(column().child(text("t", "Hola {{msr.name}}")).child(button("b", "Pagar {{total}}")));
+        let out = resolve_layout(&l, &json!({ "msr": { "name": "ANA" }, "total": "$10" }));
+        assert_eq!(out, layout(column().child(text("t", "Hola ANA")).child(button("b", "Pagar $10"))));
+        assert!(l.to_json().contains("{{msr.name}}"));
+    }
+
+    #[test]
+    fn binds_parent_and_child_paths() {
+        let l = layout(column().child(column().child(scroll("s", "Tarjeta {{msr.pan}}"))));
+        assert!(layout_binds(&l, "msr.pan"));
+        assert!(layout_binds(&l, "msr")); // padre: msr entero reemplazado
+        assert!(!layout_binds(&l, "msr.name"));
+        assert!(!layout_binds(&l, "ms"));
+
+        let l = layout(column().child(text("t", "{{msr}}")));
+        assert!(layout_binds(&l, "msr.pan")); // hijo de algo mostrado
+        assert!(!layout_binds(&l, "msrx"));
+    }
+
+    #[test]
+    fn set_path_creates_intermediates() {
+        let mut ctx = json!({ "a": 5 });
+        assert!(set_path(&mut ctx, "a.b.c", json!(1)));
+        assert!(set_path(&mut ctx, "x.y", json!("z")));
+        assert_eq!(ctx, json!({ "a": { "b": { "c": 1 } }, "x": { "y": "z" } }));
+        assert!(!set_path(&mut ctx, "x.y", json!("z")));
+        assert!(!set_path(&mut ctx, "", json!(1)));
+    }
+
+    fn set_data(st: &AppState, args: Value) -> CommandOutcome {
+        let ctx = CommandContext { state: st, message: &Value::Null, source: "test" };
+        st.commands.dispatch("ui.data.set", args, &ctx).expect("ui.data.set registrado")
+    }
+
+    #[test]
+    fn data_set_relayouts_only_bound_changes() {
+        let st = AppState::new(layout(column().child(text("t", "Total {{cart.total}}"))));
+        let relayout = |relayout| CommandOutcome::Applied { relayout };
+
+        assert_eq!(set_data(&st, json!({ "path": "cart.total", "value": "$5" })), relayout(true));
+        assert_eq!(set_data(&st, json!({ "path": "cart.total", "value": "$5" })), relayout(false)); // sin cambio
+        assert_eq!(set_data(&st, json!({ "path": "cart.items", "value": 3 })), relayout(false)); // no se muestra
+        assert_eq!(set_data(&st, json!({ "values": { "other": 1, "cart.total": "$7" } })), relayout(true));
+        assert!(st.get_layout().contains("Total $7"));
+    }
+}
@@ -0,0 +1,461 @@
+// Parser ISO 7813 de lo que entrega el lector (tracks 1, 2 y 3 en un solo string):
+//
+//   %B4111111111111111^DOE/JOHN^25121010000000000000?;4111111111111111=25121010000000000000?
+//
+// Por track: centinelas, juego de caracteres, largo máximo y LRC (si el lector lo manda:
+// los wedge normalmente no). De tracks 1 y 2 saca PAN, nombre, vencimiento y service code.
+// Un track malo no invalida a los demás: cada uno trae su Result.
+// Fuera de la pantalla sólo debe salir masked() (PAN enmascarado, resto de los tracks con *).
+
+use std::fmt;
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrackError {
+    NotRead,                           // "%E?" : el lector no pudo leer ese track
+    MissingEndSentinel,
+    InvalidChar { pos: usize, ch: char },
+    TooLong { len: usize, max: usize },
+    BadLrc { expected: char, got: char },
+    Format(String),
+}
+
+impl fmt::Display for TrackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TrackError::NotRead => write!(f, "no leído"),
+            TrackError::MissingEndSentinel => write!(f, "falta el centinela final '?'"),
+            TrackError::InvalidChar { pos, ch } => write!(f, "carácter inválido {ch:?} en la posición {pos}"),
+            TrackError::TooLong { len, max } => write!(f, "{len} caracteres (máximo {max})"),
+            TrackError::BadLrc { expected, got } => write!(f, "LRC inválido (esperado {expected:?}, llegó {got:?})"),
+            TrackError::Format(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Expiry {
+    pub year: u16, // 4 dígitos (YY + 2000)
+    pub month: u8,
+}
+
+impl fmt::Display for Expiry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02}/{:02}", self.month, self.year % 100)
+    }
+}
+
+// Lo que traen los tracks financieros (1 y 2); el track 2 no tiene nombre.
+// Debug enmascara: un {:?} en un log no puede sacar el PAN ni el discrecional.
+#[derive(Clone, PartialEq)]
+pub struct CardTrack {
+    pub pan: String,
+    pub name: Option<String>, // tal cual la banda: "DOE/JOHN A.MR"
+    pub expiry: Option<Expiry>,
+    pub service_code: Option<String>,
+    pub discretionary: String,
+    pub lrc_checked: bool,
+}
+
+#[derive(Clone, Default, PartialEq)]
+pub struct ParsedSwipe {
+    // None = el track no vino en la lectura
+    pub track1: Option<Result<CardTrack, TrackError>>,
+    pub track2: Option<Result<CardTrack, TrackError>>,
+    pub track3: Option<Result<String, TrackError>>, // track 3 no tiene formato fijo: sólo los datos
+}
+
+// Vista para mostrar / mandar a data-context (msr.*): nada que permita clonar la tarjeta
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MaskedCard {
+    pub cardholder_name: Option<String>,
+    pub pan: Option<String>,
+    pub expiry: Option<String>,
+    pub service_code: Option<String>,
+    pub track1: Option<String>,
+    pub track2: Option<String>,
+    pub track3: Option<String>,
+    pub errors: Vec<String>,
+}
+
+// ----- juego de caracteres de cada track -----
+struct Spec {
+    number: u8,
+    base: u8, // 0x20 (alfanumérico, 6 bits) o 0x30 (BCD, 4 bits)
+    mask: u8,
+    max_data: usize, // sin centinelas ni LRC
+}
+
+const TRACK1: Spec = Spec { number: 1, base: 0x20, mask: 0x3F, max_data: 76 };
+const TRACK2: Spec = Spec { number: 2, base: 0x30, mask: 0x0F, max_data: 37 };
+const TRACK3: Spec = Spec { number: 3, base: 0x30, mask: 0x0F, max_data: 104 };
+
+const START_SENTINELS: [char; 3] = ['%', ';', '+'];
+
+impl Spec {
+    fn value(&self, c: char) -> Option<u8> {
+        let b = u8::try_from(c).ok()?;
+        (b >= self.base && b - self.base <= self.mask).then(|| b - self.base)
+    }
+
+    // XOR de todos los caracteres, centinelas incluidos. `start` es el centinela que mandó
+    // el lector: el track 3 llega con ';' o '+' según el lector ('+' no está en el juego BCD,
+    // cuenta con sus 4 bits bajos, que es el código que trae la banda).
+    fn lrc(&self, start: char, data: &str) -> char {
+        let start = self.value(start).unwrap_or(start as u8 & self.mask);
+        let lrc = data
+            .chars()
+            .chain(std::iter::once('?'))
+            .filter_map(|c| self.value(c))
+            .fold(start, |acc, v| acc ^ v);
+        (lrc + self.base) as char
+    }
+
+    fn check_chars(&self, data: &str) -> Result<(), TrackError> {
+        if data.len() > self.max_data {
+            return Err(TrackError::TooLong { len: data.len(), max: self.max_data });
+        }
+        let reserved = |c: char| START_SENTINELS.contains(&c) || c == '?';
+        match data.chars().enumerate().find(|(_, c)| self.value(*c).is_none() || reserved(*c)) {
+            Some((pos, ch)) => Err(TrackError::InvalidChar { pos, ch }),
+            None => Ok(()),
+        }
+    }
+}
+
+// Un track crudo del string del lector: datos (sin centinelas) + LRC si venía
+struct RawTrack<'a> {
+    spec: &'static Spec,
+    start: char,
+    data: Result<&'a str, TrackError>,
+    lrc: Option<char>,
+}
+
+fn split_tracks(raw: &str) -> Vec<RawTrack<'_>> {
+    let mut tracks = Vec::new();
+    let mut rest = raw.trim();
+    let mut seen_t2 = false;
+    while let Some(start) = rest.find(START_SENTINELS) {
+        let sentinel = rest[start..].chars().next().unwrap_or(';');
+        let spec = match sentinel {
+            '%' => &TRACK1,
+            ';' if !seen_t2 => &TRACK2,
+            _ => &TRACK3,
+        };
+        seen_t2 |= spec.number == 2;
+        let body = &rest[start + 1..];
+        let Some(end) = body.find('?') else {
+            tracks.push(RawTrack { spec, start: sentinel, data: Err(TrackError::MissingEndSentinel), lrc: None });
+            break;
+        };
+        let data = &body[..end];
+        rest = &body[end + 1..];
+
+        // Lo que sigue al '?' es el LRC... salvo que sea el inicio del próximo track.
+        // Si coincide con un centinela sólo se toma como LRC cuando después no empieza un track.
+        let mut lrc = None;
+        if let Some(c) = rest.chars().next().filter(|c| !c.is_whitespace()) {
+            let after = rest[c.len_utf8()..].chars().next();
+            let next_is_track = START_SENTINELS.contains(&c)
+                && !(c == spec.lrc(sentinel, data) && after.is_none_or(|a| START_SENTINELS.contains(&a)));
+            if !next_is_track {
+                lrc = Some(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        tracks.push(RawTrack { spec, start: sentinel, data: Ok(data), lrc });
+    }
+    tracks
+}
+
+// Datos validados del track (centinelas, caracteres, largo, LRC); Ok(lrc_verificado)
+fn check(track: &RawTrack) -> Result<(String, bool), TrackError> {
+    let data = track.data.clone()?;
+    if data == "E" {
+        return Err(TrackError::NotRead);
+    }
+    track.spec.check_chars(data)?;
+    if let Some(got) = track.lrc {
+        let expected = track.spec.lrc(track.start, data);
+        if got != expected {
+            return Err(TrackError::BadLrc { expected, got });
+        }
+    }
+    Ok((data.to_string(), track.lrc.is_some()))
+}
+
+// --------------------- campos ---------------------
+fn digits(s: &str, n: usize, what: &str) -> Result<String, TrackError> {
+    match s.get(..n) {
+        Some(d) if d.bytes().all(|b| b.is_ascii_digit()) => Ok(d.to_string()),
+        _ => Err(TrackError::Format(format!("{what}: se esperaban {n} dígitos"))),
+    }
+}
+
+fn parse_pan(pan: &str) -> Result<String, TrackError> {
+    if !(12..=19).contains(&pan.len()) || !pan.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(TrackError::Format(format!("PAN inválido ({} caracteres)", pan.len())));
+    }
+    Ok(pan.to_string())
+}
+
+// YYMM + service code; un campo ausente va reemplazado por el separador
+fn parse_tail(mut rest: &str, sep: char) -> Result<(Option<Expiry>, Option<String>, String), TrackError> {
+    let expiry = match rest.strip_prefix(sep) {
+        Some(r) => {
+            rest = r;
+            None
+        }
+        None => {
+            let yymm = digits(rest, 4, "vencimiento")?;
+            rest = &rest[4..];
+            let month: u8 = yymm[2..].parse().unwrap_or(0);
+            if !(1..=12).contains(&month) {
+                return Err(TrackError::Format(format!("vencimiento: mes {month:02} inválido")));
+            }
+            Some(Expiry { year: 2000 + yymm[..2].parse::<u16>().unwrap_or(0), month })
+        }
+    };
+    let service_code = match rest.strip_prefix(sep) {
+        Some(r) => {
+            rest = r;
+            None
+        }
+        None => {
+            let code = digits(rest, 3, "service code")?;
+            rest = &rest[3..];
+            Some(code)
+        }
+    };
+    Ok((expiry, service_code, rest.to_string()))
+}
+
+// Track 1 (formato B): B<PAN>^<APELLIDO/NOMBRE>^<YYMM><SSS><discrecional>
+fn parse_track1(data: &str, lrc_checked: bool) -> Result<CardTrack, TrackError> {
+    let body = data.strip_prefix('B').ok_or_else(|| TrackError::Format("track 1: código de formato distinto de 'B'".to_string()))?;
+    let mut fields = body.splitn(3, '^');
+    let pan = parse_pan(fields.next().unwrap_or_default())?;
+    let name = fields.next().ok_or_else(|| TrackError::Format("track 1: falta el separador '^'".to_string()))?;
+    let tail = fields.next().ok_or_else(|| TrackError::Format("track 1: falta el segundo separador '^'".to_string()))?;
+    if !(2..=26).contains(&name.len()) {
+        return Err(TrackError::Format(format!("nombre: {} caracteres (2 a 26)", name.len())));
+    }
+    let (expiry, service_code, discretionary) = parse_tail(tail, '^')?;
+    let name = Some(name.trim().to_string()).filter(|n| !n.is_empty());
+    Ok(CardTrack { pan, name, expiry, service_code, discretionary, lrc_checked })
+}
+
+// Track 2: <PAN>=<YYMM><SSS><discrecional>
+fn parse_track2(data: &str, lrc_checked: bool) -> Result<CardTrack, TrackError> {
+    let (pan, tail) = data.split_once('=').ok_or_else(|| TrackError::Format("track 2: falta el separador '='".to_string()))?;
+    let pan = parse_pan(pan)?;
+    let (expiry, service_code, discretionary) = parse_tail(tail, '=')?;
+    Ok(CardTrack { pan, name: None, expiry, service_code, discretionary, lrc_checked })
+}
+
+pub fn parse(raw: &str) -> ParsedSwipe {
+    let mut parsed = ParsedSwipe::default();
+    for track in split_tracks(raw) {
+        let checked = check(&track);
+        match track.spec.number {
+            1 => parsed.track1 = Some(checked.and_then(|(d, lrc)| parse_track1(&d, lrc))),
+            2 => parsed.track2 = Some(checked.and_then(|(d, lrc)| parse_track2(&d, lrc))),
+            _ => parsed.track3 = Some(checked.map(|(d, _)| d)),
+        }
+    }
+    parsed
+}
+
+// 411111******1111 (primeros 6 y últimos 4, como permite PCI DSS)
+pub fn mask_pan(pan: &str) -> String {
+    if pan.len() <= 10 {
+        return "*".repeat(pan.len());
+    }
+    format!("{}{}{}", &pan[..6], "*".repeat(pan.len() - 10), &pan[pan.len() - 4..])
+}
+
+// "DOE/JOHN A.MR" → "JOHN A DOE"
+pub fn display_name(name: &str) -> String {
+    let (surname, given) = name.split_once('/').unwrap_or((name, ""));
+    let given = given.split('.').next().unwrap_or_default();
+    [given.trim(), surname.trim()].iter().filter(|p| !p.is_empty()).cloned().collect::<Vec<_>>().join(" ")
+}
+
+impl ParsedSwipe {
+    pub fn is_empty(&self) -> bool {
+        self.track1.is_none() && self.track2.is_none() && self.track3.is_none()
+    }
+
+    // Algún track se pudo leer completo
+    pub fn has_valid_track(&self) -> bool {
+        matches!(self.track1, Some(Ok(_))) || matches!(self.track2, Some(Ok(_))) || matches!(self.track3, Some(Ok(_)))
+    }
+
+    // Track 1 si está bien (trae nombre), si no track 2
+    pub fn card(&self) -> Option<&CardTrack> {
+        self.track1.as_ref().and_then(|t| t.as_ref().ok()).or_else(|| self.track2.as_ref()?.as_ref().ok())
+    }
+
+    pub fn cardholder_name(&self) -> Option<String> {
+        let t1 = self.track1.as_ref()?.as_ref().ok()?;
+        t1.name.as_deref().map(display_name).filter(|n| !n.is_empty())
+    }
+
+    // "track 1: LRC inválido (...)", y PAN distinto entre tracks 1 y 2
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let tracks = [
+            (1, self.track1.as_ref().map(|t| t.as_ref().err())),
+            (2, self.track2.as_ref().map(|t| t.as_ref().err())),
+            (3, self.track3.as_ref().map(|t| t.as_ref().err())),
+        ];
+        for (n, err) in tracks {
+            if let Some(Some(e)) = err {
+                errors.push(format!("track {n}: {e}"));
+            }
+        }
+        if let (Some(Ok(t1)), Some(Ok(t2))) = (&self.track1, &self.track2) {
+            if t1.pan != t2.pan {
+                errors.push("el PAN de los tracks 1 y 2 no coincide".to_string());
+            }
+        }
+        errors
+    }
+
+    pub fn masked(&self) -> MaskedCard {
+        let card = self.card();
+        let shown = |t: Option<Result<String, &TrackError>>| t.map(|r| r.unwrap_or_else(|e| format!("⚠ {e}")));
+        MaskedCard {
+            cardholder_name: self.cardholder_name(),
+            pan: card.map(|c| mask_pan(&c.pan)),
+            expiry: card.and_then(|c| c.expiry).map(|e| e.to_string()),
+            service_code: card.and_then(|c| c.service_code.clone()),
+            track1: shown(self.track1.as_ref().map(|t| {
+                t.as_ref().map(|c| format!("%B{}^{}^{}?", mask_pan(&c.pan), c.name.as_deref().unwrap_or_default(), stars(c)))
+            })),
+            track2: shown(self.track2.as_ref().map(|t| t.as_ref().map(|c| format!(";{}={}?", mask_pan(&c.pan), stars(c))))),
+            track3: shown(self.track3.as_ref().map(|t| t.as_ref().map(|d| format!(";{}?", "*".repeat(d.len()))))),
+            errors: self.errors(),
+        }
+    }
+}
+
+impl fmt::Debug for CardTrack {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CardTrack")
+            .field("pan", &mask_pan(&self.pan))
+            .field("name", &self.name.as_ref().map(|_| "***"))
+            .field("expiry", &self.expiry)
+            .field("service_code", &self.service_code)
+            .field("lrc_checked", &self.lrc_checked)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for ParsedSwipe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.masked(), f)
+    }
+}
+
+// Vencimiento, service code y discrecional no se muestran
+fn stars(c: &CardTrack) -> String {
+    let len = c.expiry.map_or(1, |_| 4) + c.service_code.as_ref().map_or(1, |_| 3) + c.discretionary.len();
+    "*".repeat(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const T1: &str = "%B4111111111111111^DOE/JOHN^25121010000000000000?";
+    const T2: &str = ";4111111111111111=25121010000000000000?";
+
+    fn ok<T: fmt::Debug>(t: &Option<Result<T, TrackError>>) -> &T {
+        t.as_ref().expect("track presente").as_ref().expect("track válido")
+    }
+
+    #[test]
+    fn tracks_1_and_2_without_lrc() {
+        let parsed = parse(&format!("{T1}{T2}"));
+        let t1 = ok(&parsed.track1);
+        assert_eq!(t1.pan, "4111111111111111");
+        assert_eq!(t1.name.as_deref(), Some("DOE/JOHN"));
+        assert_eq!(t1.expiry, Some(Expiry { year: 2025, month: 12 }));
+        assert_eq!(t1.service_code.as_deref(), Some("101"));
+        assert!(!t1.lrc_checked);
+        assert_eq!(ok(&parsed.track2).pan, t1.pan);
+        assert!(parsed.track3.is_none());
+        assert_eq!(parsed.cardholder_name().as_deref(), Some("JOHN DOE"));
+        assert!(parsed.errors().is_empty());
+    }
+
+    #[test]
+    fn lrc_is_checked_even_when_it_looks_like_a_sentinel() {
+        // el LRC del track 1 es ';', justo antes del ';' que abre el track 2
+        let parsed = parse(&format!("{T1};{T2}8"));
+        assert!(ok(&parsed.track1).lrc_checked);
+        assert!(ok(&parsed.track2).lrc_checked);
+
+        let bad = parse(&format!("{T2}7"));
+        assert_eq!(bad.track2, Some(Err(TrackError::BadLrc { expected: '8', got: '7' })));
+        assert_eq!(bad.errors(), ["track 2: LRC inválido (esperado '8', llegó '7')"]);
+    }
+
+    #[test]
+    fn track_3_lrc_uses_the_sentinel_that_came() {
+        for raw in [";011234567890123=4567?9", "+011234567890123=4567?9"] {
+            let parsed = parse(&format!("{T2}{raw}"));
+            assert_eq!(ok(&parsed.track3), "011234567890123=4567", "{raw}");
+        }
+        let bad = parse("+011234567890123=4567?1");
+        assert_eq!(bad.track3, Some(Err(TrackError::BadLrc { expected: '9', got: '1' })));
+    }
+
+    #[test]
+    fn unreadable_and_truncated_tracks() {
+        let parsed = parse(&format!("%E?{T2}"));
+        assert_eq!(parsed.track1, Some(Err(TrackError::NotRead)));
+        assert!(parsed.has_valid_track());
+        assert_eq!(parsed.card().map(|c| c.pan.as_str()), Some("4111111111111111"));
+        assert!(parsed.cardholder_name().is_none());
+
+        let cut = parse(";4111111111111111=2512");
+        assert_eq!(cut.track2, Some(Err(TrackError::MissingEndSentinel)));
+        assert!(!cut.has_valid_track());
+        assert!(parse("").is_empty());
+    }
+
+    #[test]
+    fn too_long_and_invalid_chars() {
+        let long = parse(&format!(";{}?", "1".repeat(38)));
+        assert_eq!(long.track2, Some(Err(TrackError::TooLong { len: 38, max: 37 })));
+        let letters = parse(";41111111A1111111=2512101?");
+        assert_eq!(letters.track2, Some(Err(TrackError::InvalidChar { pos: 8, ch: 'A' })));
+    }
+
+    #[test]
+    fn pan_mismatch_between_tracks_is_reported() {
+        let parsed = parse(&format!("{T1};4111111111111112=25121010000000000000?"));
+        assert_eq!(parsed.errors(), ["el PAN de los tracks 1 y 2 no coincide"]);
+    }
+
+    #[test]
+    fn masking_and_names() {
+        assert_eq!(mask_pan("4111111111111111"), "411111******1111");
+        assert_eq!(mask_pan("12345"), "*****");
+        assert_eq!(display_name("DOE/JOHN A.MR"), "JOHN A DOE");
+        assert_eq!(display_name("DOE/"), "DOE");
+        assert_eq!(display_name("CARDHOLDER"), "CARDHOLDER");
+
+        let parsed = parse(&format!("{T1}{T2}"));
+        let masked = parsed.masked();
+        assert_eq!(masked.pan.as_deref(), Some("411111******1111"));
+        assert_eq!(masked.expiry.as_deref(), Some("12/25"));
+        for shown in [format!("{masked:?}"), format!("{parsed:?}"), format!("{:?}", ok(&parsed.track1))] {
+            assert!(!shown.contains("4111111111111111") && !shown.contains("10000000000000"), "{shown}");
+        }
+    }
+}
@@ -0,0 +1,463 @@
+// Eventos de UI hacia el backend (DEALER → ROUTER del broker).
+// Lo que on_ui_event no resuelve localmente (o lo que está en upstream.forward_events)
+// sale con device / pantalla / evento / inputs; la respuesta del backend es un mensaje
+// de broker normal (layout o cmd) y pasa por el mismo camino que el SUB:
+// firma, dedup, registry de comandos y ACK.
+//
+// Por el mismo DEALER van los requests que necesitan respuesta directa (autorizar un
+// pago, buscar un cliente): salen como { "type": "ui.request", "corr_id", "method", "params" }
+// y el backend contesta con el mismo corr_id (top-level o en envelope):
+//
+//   { "type": "ui.reply", "corr_id": "...", "result": {...} }   o   { ..., "error": "..." }
+//
+// La respuesta despierta al que espera en request(); si además trae cmd/content se aplica
+// como cualquier mensaje del broker. Lo que llega después del timeout se descarta.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::Value;
+use tauri::AppHandle;
+use tokio::sync::oneshot;
+
+use crate::broker::handle_frames;
+use crate::curve::CurveKeys;
+use crate::signature::Keyring;
+use crate::state::AppState;
+
+const POLL_MS: i64 = 100;
+const RECONNECT_WAIT_MS: u64 = 2_000;
+static EVENT_SEQ: AtomicU64 = AtomicU64::new(0);
+static REQUEST_SEQ: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UiEvent {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub msg_id: String,
+    pub device_id: String,
+    pub screen_id: Option<String>,
+    pub event_id: String,
+    pub inputs: HashMap<String, String>,
+    pub at_millis: i64,
+}
+
+impl UiEvent {
+    pub fn new(device_id: String, screen_id: Option<String>, event_id: &str, inputs: HashMap<String, String>) -> Self {
+        let at_millis = Utc::now().timestamp_millis();
+        let seq = EVENT_SEQ.fetch_add(1, Ordering::Relaxed);
+        Self {
+            kind: "ui.event",
+            msg_id: format!("{device_id}-{at_millis}-{seq}"),
+            device_id,
+            screen_id,
+            event_id: event_id.to_string(),
+            // las claves "__*" son internas del Renderer (respaldo del password, etc.)
+            inputs: inputs.into_iter().filter(|(k, _)| !k.starts_with("__")).collect(),
+            at_millis,
+        }
+    }
+}
+
+// Qué se hace con un evento de UI después de on_ui_event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventRoute {
+    Local,     // lo maneja el device y no está en forward_events
+    Forward,   // sale al backend
+    Unhandled, // nadie lo maneja y no hay upstream: sólo queda el log
+}
+
+// Lo no manejado localmente va al backend; lo manejado sólo si está en upstream.forward_events
+pub fn route_event(event_id: &str, handled: bool, forward_events: &[String], upstream_enabled: bool) -> EventRoute {
+    let wanted = !handled || forward_events.iter().any(|e| e == event_id);
+    match (wanted && upstream_enabled, handled) {
+        (true, _) => EventRoute::Forward,
+        (false, true) => EventRoute::Local,
+        (false, false) => EventRoute::Unhandled,
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UiRequest {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub corr_id: String,
+    pub device_id: String,
+    pub method: String,
+    pub params: Value,
+    pub timeout_ms: u64, // para que el backend no conteste algo que ya nadie espera
+    pub at_millis: i64,
+}
+
+impl UiRequest {
+    pub fn new(device_id: String, method: &str, params: Value, timeout: Duration) -> Self {
+        let at_millis = Utc::now().timestamp_millis();
+        let seq = REQUEST_SEQ.fetch_add(1, Ordering::Relaxed);
+        Self {
+            kind: "ui.request",
+            corr_id: format!("{device_id}-req-{at_millis}-{seq}"),
+            device_id,
+            method: method.to_string(),
+            params,
+            timeout_ms: timeout.as_millis() as u64,
+            at_millis,
+        }
+    }
+}
+
+enum Outgoing {
+    Event(UiEvent),
+    Request(UiRequest),
+}
+
+// Por qué no hubo result: el que llama decide distinto si el backend contestó que no,
+// si no contestó a tiempo o si ni siquiera se pudo preguntar
+#[derive(Debug, Clone, PartialEq)]
+pub enum RequestError {
+    Unavailable(String), // sin upstream, no se pudo enviar, se apagó o respuesta no autenticada
+    Timeout(String),
+    Remote(String), // el backend contestó con "error"
+}
+
+impl std::fmt::Display for RequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RequestError::Unavailable(e) | RequestError::Timeout(e) | RequestError::Remote(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+type Reply = Result<Value, RequestError>;
+
+// corr_id → quien espera la respuesta
+type Pending = Arc<Mutex<HashMap<String, oneshot::Sender<Reply>>>>;
+
+// Cola hacia el hilo del DEALER (los sockets zmq no se comparten entre hilos)
+#[derive(Clone, Default)]
+pub struct UpstreamHandle {
+    tx: Arc<Mutex<Option<Sender<Outgoing>>>>,
+    pending: Pending,
+}
+
+impl UpstreamHandle {
+    pub fn is_enabled(&self) -> bool {
+        self.tx.lock().unwrap().is_some()
+    }
+
+    pub fn send(&self, event: UiEvent) -> Result<(), String> {
+        self.enqueue(Outgoing::Event(event))
+    }
+
+    fn enqueue(&self, out: Outgoing) -> Result<(), String> {
+        let tx = self.tx.lock().unwrap();
+        let tx = tx.as_ref().ok_or("upstream no configurado (upstream.endpoint)")?;
+        tx.send(out).map_err(|_| "el hilo upstream terminó".to_string())
+    }
+
+    // Envía el request y espera su respuesta (result del backend, o su error / timeout).
+    // Normalmente se llama vía AppState::request, que pone device_id y el timeout por defecto.
+    pub async fn request(&self, request: UiRequest) -> Result<Value, RequestError> {
+        let corr_id = request.corr_id.clone();
+        let method = request.method.clone();
+        let timeout = Duration::from_millis(request.timeout_ms);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(corr_id.clone(), tx);
+        if let Err(e) = self.enqueue(Outgoing::Request(request)) {
+            self.pending.lock().unwrap().remove(&corr_id);
+            return Err(RequestError::Unavailable(e));
+        }
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(reply)) => reply,
+            Ok(Err(_)) => Err(RequestError::Unavailable(format!("{method}: upstream desactivado antes de la respuesta"))),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&corr_id);
+                Err(RequestError::Timeout(format!("{method}: sin respuesta en {}ms ({corr_id})", timeout.as_millis())))
+            }
+        }
+    }
+
+    fn resolve(&self, corr_id: &str, reply: Reply) -> bool {
+        match self.pending.lock().unwrap().remove(corr_id) {
+            Some(waiter) => waiter.send(reply).is_ok(),
+            None => false,
+        }
+    }
+
+    // Al apagarse el upstream: los que esperan reciben error de inmediato (no esperan al timeout)
+    fn fail_all(&self) {
+        self.pending.lock().unwrap().clear();
+    }
+}
+
+// No hace nada si upstream.endpoint no está configurado
+pub fn start_upstream(app: AppHandle, state: AppState) {
+    if state.config().upstream.endpoint.is_none() {
+        return;
+    }
+    let (tx, rx) = mpsc::channel();
+    *state.upstream.tx.lock().unwrap() = Some(tx);
+    std::thread::spawn(move || {
+        let ctx = zmq::Context::new();
+        loop {
+            let Some(endpoint) = state.config().upstream.endpoint else {
+                eprintln!("[UP] upstream desactivado por config");
+                *state.upstream.tx.lock().unwrap() = None;
+                state.upstream.fail_all();
+                return;
+            };
+            match run_dealer(&app, &state, &ctx, &endpoint, &rx) {
+                Ok(true) => eprintln!("[UP] upstream.endpoint cambió"),
+                Ok(false) => return, // se soltó el Sender: nadie más va a enviar
+                Err(e) => {
+                    eprintln!("[UP] ❌ {e}; reintento en {RECONNECT_WAIT_MS}ms");
+                    std::thread::sleep(std::time::Duration::from_millis(RECONNECT_WAIT_MS));
+                }
+            }
+        }
+    });
+}
+
+// Ok(true) = reconectar (cambió el endpoint); Ok(false) = canal cerrado, terminar
+fn run_dealer(
+    app: &AppHandle,
+    state: &AppState,
+    ctx: &zmq::Context,
+    endpoint: &str,
+    rx: &Receiver<Outgoing>,
+) -> Result<bool, String> {
+    let cfg = state.config();
+    let socket = ctx.socket(zmq::DEALER).map_err(|e| format!("no se pudo crear DEALER: {e}"))?;
+    socket.set_linger(0).map_err(|e| e.to_string())?;
+    // el ROUTER del backend enruta las respuestas por esta identidad
+    socket.set_identity(cfg.device_id().as_bytes()).map_err(|e| e.to_string())?;
+    if let Some(keys) = CurveKeys::load(cfg.broker.curve_keys_file.as_deref())? {
+        keys.apply(&socket)?;
+    }
+    socket.connect(endpoint).map_err(|e| format!("no se pudo conectar a {endpoint}: {e}"))?;
+    let keyring = Keyring::load(cfg.broker.keys_file.as_deref(), cfg.broker.require_signed);
+    eprintln!("[UP] DEALER conectado a {endpoint}");
+
+    loop {
+        // salientes
+        loop {
+            match rx.try_recv() {
+                Ok(Outgoing::Event(event)) => {
+                    let body = serde_json::to_vec(&event).map_err(|e| e.to_string())?;
+                    match socket.send(body, zmq::DONTWAIT) {
+                        Ok(()) => eprintln!("[UP] → {} ({})", event.event_id, event.msg_id),
+                        // cola del DEALER llena (backend caído hace rato): el evento se pierde
+                        Err(e) => eprintln!("[UP] ❌ evento {} descartado: {e}", event.event_id),
+                    }
+                }
+                Ok(Outgoing::Request(request)) => {
+                    let body = serde_json::to_vec(&request).map_err(|e| e.to_string())?;
+                    match socket.send(body, zmq::DONTWAIT) {
+                        Ok(()) => eprintln!("[UP] → request {} ({})", request.method, request.corr_id),
+                        Err(e) => {
+                            let error = format!("{}: no se pudo enviar: {e}", request.method);
+                            state.upstream.resolve(&request.corr_id, Err(RequestError::Unavailable(error)));
+                        }
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Ok(false),
+            }
+        }
+
+        // respuestas del backend → mismo camino que los frames del SUB
+        if socket.poll(zmq::POLLIN, POLL_MS).map_err(|e| format!("poll: {e}"))? > 0 {
+            let frames = socket.recv_multipart(zmq::DONTWAIT).map_err(|e| format!("error recibiendo: {e}"))?;
+            if !take_reply(state, &keyring, &frames) {
+                handle_frames(app, state, &keyring, &frames);
+            }
+        }
+
+        if state.config().upstream.endpoint.as_deref() != Some(endpoint) {
+            return Ok(true);
+        }
+    }
+}
+
+// Respuesta a un request (trae corr_id) → al que espera. true = ya no es un mensaje de broker.
+fn take_reply(state: &AppState, keyring: &Keyring, frames: &[Vec<u8>]) -> bool {
+    let Some(reply) = frames.iter().rev().find_map(|b| serde_json::from_slice::<Value>(b).ok()) else {
+        return false;
+    };
+    let Some(corr_id) = reply
+        .get("corr_id")
+        .or_else(|| reply.get("envelope")?.get("corr_id"))
+        .and_then(|v| v.as_str())
+    else {
+        return false;
+    };
+
+    // misma regla de firma que el resto: una autorización falsa es peor que un layout falso.
+    // Lo no autenticado se descarta sin tocar al que espera: si no, cualquiera que conozca
+    // el corr_id cortaría la respuesta buena con un error.
+    if let Err(e) = keyring.verify(&reply) {
+        eprintln!("[UP] 🔐 respuesta descartada ({corr_id}): {e}");
+        return true;
+    }
+    let result = match reply.get("error").filter(|e| !e.is_null()) {
+        Some(Value::String(e)) => Err(RequestError::Remote(e.clone())),
+        Some(other) => Err(RequestError::Remote(other.to_string())),
+        None => Ok(reply.get("result").cloned().unwrap_or(Value::Null)),
+    };
+    if !state.upstream.resolve(corr_id, result) {
+        eprintln!("[UP] ⏭️ respuesta sin request pendiente (tardía o duplicada): {corr_id}");
+    }
+    // cmd / content en la respuesta: también se aplica (handle_frames vuelve a verificar)
+    !(reply.get("cmd").is_some() || reply.get("content").is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{column, layout, text};
+    use serde_json::json;
+
+    // Handle con cola pero sin hilo DEALER: el test hace de backend con `rx`
+    fn handle() -> (UpstreamHandle, Receiver<Outgoing>) {
+        let (tx, rx) = mpsc::channel();
+        let handle = UpstreamHandle::default();
+        *handle.tx.lock().unwrap() = Some(tx);
+        (handle, rx)
+    }
+
+    fn request(ms: u64) -> UiRequest {
+        UiRequest::new("pos-1".to_string(), "payment.authorize", Value::Null, Duration::from_millis(ms))
+    }
+
+    fn corr_id(rx: &Receiver<Outgoing>) -> String {
+        match rx.recv_timeout(Duration::from_secs(1)).unwrap() {
+            Outgoing::Request(r) => r.corr_id,
+            Outgoing::Event(_) => panic!("se esperaba un request"),
+        }
+    }
+
+    #[tokio::test]
+    async fn errors_say_why_there_was_no_result() {
+        let off = UpstreamHandle::default().request(request(1000)).await;
+        assert!(matches!(off, Err(RequestError::Unavailable(_))), "{off:?}");
+
+        let (up, rx) = handle();
+        let late = up.request(request(50)).await;
+        assert!(matches!(late, Err(RequestError::Timeout(_))), "{late:?}");
+        // lo que llega después del timeout no tiene a quién ir
+        assert!(!up.resolve(&corr_id(&rx), Ok(Value::Null)));
+
+        let waiting = tokio::spawn({
+            let up = up.clone();
+            async move { up.request(request(5000)).await }
+        });
+        let id = tokio::task::spawn_blocking(move || corr_id(&rx)).await.unwrap();
+        assert!(up.resolve(&id, Err(RequestError::Remote("tarjeta bloqueada".into()))));
+        assert_eq!(waiting.await.unwrap(), Err(RequestError::Remote("tarjeta bloqueada".into())));
+    }
+
+    // take_reply con un request pendiente en `state`; devuelve (consumido, lo que recibió el que espera)
+    async fn reply_to_pending(
+        keyring: &Keyring,
+        reply: impl FnOnce(&str) -> Value,
+    ) -> (bool, Option<Result<Value, RequestError>>) {
+        let state = AppState::new(layout(column().child(text("t", "inicio"))));
+        let (tx, rx) = mpsc::channel();
+        *state.upstream.tx.lock().unwrap() = Some(tx);
+        let waiting = tokio::spawn({
+            let up = state.upstream.clone();
+            async move { up.request(request(300)).await }
+        });
+        let id = tokio::task::spawn_blocking(move || corr_id(&rx)).await.unwrap();
+        let frame = serde_json::to_vec(&reply(&id)).unwrap();
+        let consumed = take_reply(&state, keyring, &[b"".to_vec(), frame]);
+        let got = match waiting.await.unwrap() {
+            Err(RequestError::Timeout(_)) => None,
+            other => Some(other),
+        };
+        (consumed, got)
+    }
+
+    #[tokio::test]
+    async fn replies_resolve_the_waiter() {
+        let open = Keyring::default();
+        let (consumed, got) = reply_to_pending(&open, |id| json!({ "type": "ui.reply", "corr_id": id, "result": { "ok": 1 } })).await;
+        assert!(consumed);
+        assert_eq!(got, Some(Ok(json!({ "ok": 1 }))));
+
+        let (_, got) = reply_to_pending(&open, |id| json!({ "corr_id": id, "error": "tarjeta bloqueada" })).await;
+        assert_eq!(got, Some(Err(RequestError::Remote("tarjeta bloqueada".into()))));
+
+        let (_, got) = reply_to_pending(&open, |id| json!({ "envelope": { "corr_id": id }, "error": { "code": 51 } })).await;
+        assert_eq!(got, Some(Err(RequestError::Remote(r#"{"code":51}"#.into()))));
+    }
+
+    #[tokio::test]
+    async fn unauthenticated_reply_is_dropped() {
+        let signed_only = Keyring::load(None, true);
+        let (consumed, got) = reply_to_pending(&signed_only, |id| json!({ "corr_id": id, "error": "cancelado" })).await;
+        // no llega al que espera (que termina por timeout) ni pasa a handle_frames
+        assert!(consumed);
+        assert_eq!(got, None);
+    }
+
+    #[tokio::test]
+    async fn reply_with_cmd_goes_on_to_handle_frames() {
+        let open = Keyring::default();
+        let (consumed, got) =
+            reply_to_pending(&open, |id| json!({ "corr_id": id, "result": null, "cmd": { "name": "ui.screen.show" } })).await;
+        assert!(!consumed);
+        assert_eq!(got, Some(Ok(Value::Null)));
+
+        let state = AppState::new(layout(column().child(text("t", "x"))));
+        // sin corr_id no es respuesta: mensaje de broker normal
+        let broker_msg = serde_json::to_vec(&json!({ "cmd": { "name": "ui.apply" } })).unwrap();
+        assert!(!take_reply(&state, &open, &[broker_msg]));
+    }
+
+    #[test]
+    fn event_drops_internal_inputs() {
+        let inputs = HashMap::from([
+            ("pay_amount".to_string(), "10.00".to_string()),
+            ("__pwd_backup".to_string(), "secreto".to_string()),
+            ("__".to_string(), "x".to_string()),
+        ]);
+        let ev = UiEvent::new("pos-1".to_string(), Some("pago".to_string()), "btn_ok", inputs);
+        assert_eq!(ev.inputs, HashMap::from([("pay_amount".to_string(), "10.00".to_string())]));
+        assert!(ev.msg_id.starts_with("pos-1-"));
+        let other = UiEvent::new("pos-1".to_string(), None, "btn_ok", HashMap::new());
+        assert_ne!(ev.msg_id, other.msg_id);
+    }
+
+    #[test]
+    fn routing_of_ui_events() {
+        let forward = vec!["btn_read_msr".to_string()];
+        // no manejado: al backend si hay upstream, si no sólo el log
+        assert_eq!(route_event("btn_x", false, &forward, true), EventRoute::Forward);
+        assert_eq!(route_event("btn_x", false, &forward, false), EventRoute::Unhandled);
+        // manejado: sólo sale si está en forward_events
+        assert_eq!(route_event("nav_back", true, &forward, true), EventRoute::Local);
+        assert_eq!(route_event("btn_read_msr", true, &forward, true), EventRoute::Forward);
+        assert_eq!(route_event("btn_read_msr", true, &forward, false), EventRoute::Local);
+    }
+
+    #[test]
+    fn forwarded_event_reaches_the_dealer_queue() {
+        let (up, rx) = handle();
+        let inputs = HashMap::from([("__pwd".to_string(), "x".to_string())]);
+        up.send(UiEvent::new("pos-1".to_string(), None, "btn_x", inputs)).unwrap();
+        match rx.try_recv().unwrap() {
+            Outgoing::Event(ev) => {
+                assert_eq!(ev.event_id, "btn_x");
+                assert!(ev.inputs.is_empty());
+                assert_eq!(serde_json::to_value(&ev).unwrap()["type"], "ui.event");
+            }
+            Outgoing::Request(_) => panic!("se esperaba un evento"),
+        }
+        assert!(UpstreamHandle::default().send(UiEvent::new("pos-1".to_string(), None, "btn_x", HashMap::new())).is_err());
+    }
+}
@@ -0,0 +1,234 @@
+// Validación estructural del layout antes de promoverlo a current / last_good.
+// Devuelve TODAS las violaciones encontradas (con ruta tipo "$.root.children[2]"),
+// no sólo la primera como haría serde.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::layout::{UiLayout, UiNode};
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Violation {
+    pub path: String,
+    pub message: String,
+}
+
+impl Violation {
+    pub fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { path: path.into(), message: message.into() }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+// Para logs: "a: x; b: y"
+pub fn describe(violations: &[Violation]) -> String {
+    violations.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("; ")
+}
+
+// --------------------- JSON crudo → UiLayout ---------------------
+pub fn parse_layout_value(v: &Value) -> Result<UiLayout, Vec<Violation>> {
+    let mut out = Vec::new();
+
+    let Some(obj) = v.as_object() else {
+        return Err(vec![Violation::new("$", "el layout debe ser un objeto")]);
+    };
+    match obj.get("root") {
+        Some(root) => check_node_value(root, "$.root", &mut out),
+        None => out.push(Violation::new("$.root", "falta el nodo raíz")),
+    }
+    if !out.is_empty() {
+        return Err(out);
+    }
+
+    // nodos OK; lo que falle aquí es de los campos de primer nivel
+    let layout: UiLayout = serde_json::from_value(v.clone())
+        .map_err(|e| vec![Violation::new("$", e.to_string())])?;
+    validate_layout(&layout)?;
+    Ok(layout)
+}
+
+// Deserializa cada nodo por separado (con children vacío) para poder
+// reportar la ruta exacta de cada error y seguir con los hermanos.
+fn check_node_value(node: &Value, path: &str, out: &mut Vec<Violation>) {
+    let Some(obj) = node.as_object() else {
+        out.push(Violation::new(path, "el nodo debe ser un objeto"));
+        return;
+    };
+    let mut shallow = obj.clone();
+    if let Some(children) = obj.get("children") {
+        match children.as_array() {
+            Some(items) => {
+                for (i, child) in items.iter().enumerate() {
+                    check_node_value(child, &format!("{path}.children[{i}]"), out);
+                }
+                shallow.insert("children".into(), Value::Array(vec![]));
+            }
+            None => {
+                out.push(Violation::new(format!("{path}.children"), "children debe ser un arreglo"));
+                shallow.remove("children");
+            }
+        }
+    }
+    if let Err(e) = serde_json::from_value::<UiNode>(Value::Object(shallow)) {
+        out.push(Violation::new(path, e.to_string()));
+    }
+}
+
+// --------------------- reglas semánticas sobre el modelo tipado ---------------------
+pub fn validate_layout(layout: &UiLayout) -> Result<(), Vec<Violation>> {
+    let mut out = Vec::new();
+
+    check_color(layout.background.as_deref(), "$.background", &mut out);
+    if let Some(cd) = &layout.customer_display {
+        check_color(cd.bg_color.as_deref(), "$.customer_display.bg_color", &mut out);
+    }
+
+    let mut ids: HashMap<String, String> = HashMap::new();
+    check_node(&layout.root, "$.root", &mut ids, &mut out);
+
+    if out.is_empty() { Ok(()) } else { Err(out) }
+}
+
+fn check_node(node: &UiNode, path: &str, ids: &mut HashMap<String, String>, out: &mut Vec<Violation>) {
+    if let Some(id) = node.id() {
+        if id.trim().is_empty() {
+            out.push(Violation::new(format!("{path}.id"), "id vacío"));
+        } else if let Some(first) = ids.get(id) {
+            out.push(Violation::new(format!("{path}.id"), format!("id duplicado '{id}' (ya usado en {first})")));
+        } else {
+            ids.insert(id.to_string(), path.to_string());
+        }
+    }
+
+    match node {
+        UiNode::Text(t) => check_color(t.color.as_deref(), &format!("{path}.color"), out),
+        UiNode::Button(b) => {
+            check_color(b.tint.as_deref(), &format!("{path}.tint"), out);
+            check_color(b.text_color.as_deref(), &format!("{path}.text_color"), out);
+        }
+        UiNode::Scroll(s) => check_color(s.color.as_deref(), &format!("{path}.color"), out),
+        UiNode::Column(c) => {
+            check_color(c.background.as_deref(), &format!("{path}.background"), out);
+            if c.children.is_empty() {
+                out.push(Violation::new(format!("{path}.children"), "una columna debe tener al menos un hijo"));
+            }
+            for (i, child) in c.children.iter().enumerate() {
+                check_node(child, &format!("{path}.children[{i}]"), ids, out);
+            }
+        }
+        UiNode::Spacer(_)
+        | UiNode::Logo(_)
+        | UiNode::InputMoney(_)
+        | UiNode::InputText(_)
+        | UiNode::InputPassword(_) => {}
+    }
+}
+
+// #RGB, #RRGGBB o #RRGGBBAA
+pub fn is_valid_color(c: &str) -> bool {
+    let Some(hex) = c.strip_prefix('#') else { return false };
+    matches!(hex.len(), 3 | 6 | 8) && hex.chars().all(|ch| ch.is_ascii_hexdigit())
+}
+
+fn check_color(c: Option<&str>, path: &str, out: &mut Vec<Violation>) {
+    if let Some(c) = c {
+        if !is_valid_color(c) {
+            out.push(Violation::new(path, format!("color inválido '{c}' (se espera #RGB, #RRGGBB o #RRGGBBAA)")));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{button, column, layout, text};
+    use serde_json::json;
+
+    fn violations(v: Value) -> Vec<Violation> {
+        parse_layout_value(&v).expect_err("el layout debería ser inválido")
+    }
+
+    fn paths(vs: &[Violation]) -> Vec<&str> {
+        vs.iter().map(|v| v.path.as_str()).collect()
+    }
+
+    #[test]
+    fn valid_layout_parses() {
+        let v = json!({"root": {"type": "column", "children": [{"type": "text", "id": "t", "text": "hola"}]}});
+        assert_eq!(parse_layout_value(&v).unwrap(), layout(column().child(text("t", "hola"))));
+    }
+
+    #[test]
+    fn non_object_root() {
+        assert_eq!(violations(json!(5)), vec![Violation::new("$", "el layout debe ser un objeto")]);
+        assert_eq!(violations(json!({})), vec![Violation::new("$.root", "falta el nodo raíz")]);
+        assert_eq!(violations(json!({"root": 5})), vec![Violation::new("$.root", "el nodo debe ser un objeto")]);
+    }
+
+    #[test]
+    fn unknown_type_and_missing_text_are_reported_per_node() {
+        let vs = violations(json!({"root": {"type": "column", "children": [
+            {"type": "text", "text": "ok"},
+            {"type": "marquee", "text": "x"},
+            {"type": "text", "id": "sin_texto"},
+        ]}}));
+        assert_eq!(paths(&vs), ["$.root.children[1]", "$.root.children[2]"]);
+        assert!(vs[0].message.contains("marquee"), "{}", vs[0]);
+        assert!(vs[1].message.contains("text"), "{}", vs[1]);
+    }
+
+    #[test]
+    fn children_must_be_an_array() {
+        let vs = violations(json!({"root": {"type": "column", "children": {"type": "text", "text": "x"}}}));
+        // además la columna queda sin children, que es obligatorio
+        assert_eq!(paths(&vs), ["$.root.children", "$.root"]);
+        assert_eq!(vs[0].message, "children debe ser un arreglo");
+    }
+
+    #[test]
+    fn colors() {
+        for ok in ["#fff", "#FFFFFF", "#00000080"] {
+            assert!(is_valid_color(ok), "{ok}");
+        }
+        for bad in ["fff", "#ff", "#ffff", "#fffffff", "#ggg", "red", "", "#"] {
+            assert!(!is_valid_color(bad), "{bad}");
+        }
+
+        let l = layout(column().background("blue").child(text("t", "x").color("#12")).child(button("b", "ok").tint("#zzz")))
+            .background("#000");
+        let vs = validate_layout(&l).unwrap_err();
+        assert_eq!(paths(&vs), ["$.root.background", "$.root.children[0].color", "$.root.children[1].tint"]);
+        assert!(vs[0].message.contains("'blue'"));
+    }
+
+    #[test]
+    fn empty_column() {
+        let vs = validate_layout(&layout(column().child(column()))).unwrap_err();
+        assert_eq!(vs, vec![Violation::new("$.root.children[0].children", "una columna debe tener al menos un hijo")]);
+    }
+
+    #[test]
+    fn duplicate_ids_name_both_paths() {
+        let l = layout(column().child(text("a", "1")).child(column().child(text("b", "2")).child(button("a", "3"))));
+        let vs = validate_layout(&l).unwrap_err();
+        assert_eq!(
+            vs,
+            vec![Violation::new("$.root.children[1].children[1].id", "id duplicado 'a' (ya usado en $.root.children[0])")]
+        );
+    }
+
+    #[test]
+    fn all_violations_are_collected() {
+        let l = layout(column().child(text(" ", "x").color("nope")).child(column())).background("negro");
+        let vs = validate_layout(&l).unwrap_err();
+        assert_eq!(vs.len(), 4);
+        assert_eq!(describe(&vs[..1]), "$.background: color inválido 'negro' (se espera #RGB, #RRGGBB o #RRGGBBAA)");
+    }
+}
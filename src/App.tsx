@@ -28,6 +28,33 @@ export default function App() {
     };
   }, []);
 
+  // 2b. lector MSR tipo wedge: mientras hay una lectura activa, lo que "teclea"
+  // el lector se junta aquí y se manda a Rust al Enter (msr_wedge_input)
+  useEffect(() => {
+    let capturing = false;
+    let buffer = "";
+    const unlistenPromise = listen<{ reading: boolean; reader: string }>("msr_state", (event) => {
+      capturing = event.payload.reading && event.payload.reader === "wedge";
+      buffer = "";
+    });
+    const onKey = (e: KeyboardEvent) => {
+      if (!capturing) return;
+      e.preventDefault(); // que la banda no termine escrita en un input
+      if (e.key === "Enter") {
+        const data = buffer;
+        buffer = "";
+        invoke("msr_wedge_input", { data }).catch((err) => console.warn("[MSR]", err));
+      } else if (e.key.length === 1) {
+        buffer += e.key;
+      }
+    };
+    window.addEventListener("keydown", onKey);
+    return () => {
+      window.removeEventListener("keydown", onKey);
+      unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, []);
+
   // 3. render dinámico
   return <Renderer layout={layout} />;
 }
@@ -150,9 +150,9 @@ if (eventId.startsWith("nav_to:")) {
   return; // no invoques on_ui_event con nav_to
 }
 
-  // 2) Eventos “de negocio” sí van a Rust
+  // 2) Eventos “de negocio” sí van a Rust (con los inputs actuales, por si van al backend)
   try {
-    await invoke("on_ui_event", { eventId });
+    await invoke("on_ui_event", { eventId, inputs: ctx.inputs });
   } catch {
     /* opcional: ignora si aún no implementaste el handler */
   }
@@ -227,6 +227,8 @@ if (eventId.startsWith("nav_to:")) {
 
     case "input_money": {
       const m = node as InputMoneyNode;
+      const id = m.id || "input_money";
+      const value = ctx.inputs[id] ?? m.value ?? "";
       const style: React.CSSProperties = {
         alignSelf: m.align ? alignToFlex(m.align) : undefined,
         borderRadius: 8,
@@ -240,7 +242,8 @@ if (eventId.startsWith("nav_to:")) {
         <input
           key={key}
           placeholder={m.hint || "Monto"}
-          defaultValue={m.value || ""}
+          value={value}
+          onChange={(e) => ctx.setInput(id, e.currentTarget.value)}
           style={style}
         />
       );